@@ -0,0 +1,327 @@
+// Helpers and precomputed attack tables for working with bitboards
+// A bitboard has one bit per square, with the most significant bit being A8, (0, 0), and the least significant H1,
+// so the square at index 8 * y + x is the bit 0x80_00_00_00_00_00_00_00 >> index
+
+use std::sync::OnceLock;
+
+use crate::{Color, Piece, Square};
+
+// Returns the bitboard with only the square at the given numerical coordinates set
+pub(crate) const fn square_bit(x : usize, y : usize) -> u64 {
+    0x80_00_00_00_00_00_00_00u64 >> (8 * y + x)
+}
+
+// Returns the index 8 * y + x of the first square set in a non-empty bitboard
+pub(crate) fn first_index(bits : u64) -> usize {
+    bits.leading_zeros() as usize
+}
+
+// Iterates over the numerical coordinates of every square set in a bitboard, starting from A8
+pub(crate) fn squares(mut bits : u64) -> impl Iterator<Item = (usize, usize)> {
+    std::iter::from_fn(move || {
+        if bits == 0 {
+            return None;
+        }
+        let index = first_index(bits);
+        bits &= !(0x80_00_00_00_00_00_00_00u64 >> index);
+        return Some((index % 8, index / 8))
+    })
+}
+
+// Returns the squares set in a bitboard, starting from A8
+pub(crate) fn bits_to_squares(bits : u64) -> Vec<Square> {
+    squares(bits).map(|(x, y)| Square::at(x, y)).collect()
+}
+
+// Index of a color in tables that have one entry per color
+pub(crate) const fn color_index(color : Color) -> usize {
+    match color {
+        Color::Black => {0},
+        Color::White => {1}
+    }
+}
+
+// Index of a piece type in tables that have one entry per piece type
+pub(crate) const fn piece_index(piece : Piece) -> usize {
+    piece as usize
+}
+
+const KING_OFFSETS : [(i32, i32); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+const KNIGHT_OFFSETS : [(i32, i32); 8] = [(-1, -2), (1, -2), (-2, -1), (2, -1), (-2, 1), (2, 1), (-1, 2), (1, 2)];
+const BLACK_PAWN_OFFSETS : [(i32, i32); 2] = [(-1, 1), (1, 1)];
+const WHITE_PAWN_OFFSETS : [(i32, i32); 2] = [(-1, -1), (1, -1)];
+// Magic numbers for every square, found by trying random numbers with few bits set until one had no collisions
+// Published magic numbers assume a different bit order from this crate's, so these were searched for separately
+const ROOK_MAGICS : [u64; 64] = [
+    0x0004_0221_8504_0042, 0x01A0_2210_3900_8804, 0x0241_0004_0008_A251, 0x3012_0009_0410_2002,
+    0x006A_0040_0820_1106, 0x0920_4010_0A00_2082, 0x0020_8040_0100_2011, 0x0044_B104_8004_4021,
+    0x1042_0061_0084_0200, 0x1005_8002_0001_0080, 0x120A_0005_1008_E200, 0x0002_0800_1101_0500,
+    0x0412_8110_0488_0080, 0x0001_0840_1020_0100, 0x8642_4002_2104_8100, 0x0130_4002_8009_2080,
+    0x8010_4082_450A_0004, 0x0002_0004_0142_0088, 0x941A_0010_2004_0400, 0x80C0_0800_0501_0010,
+    0x608C_1000_0800_8080, 0x0002_0048_2082_0010, 0x2180_5000_2002_4000, 0x0180_0020_01D1_4000,
+    0x4208_0069_0200_0084, 0xA020_8802_0400_2110, 0x0000_1020_0801_04C0, 0x0824_0080_0808_0040,
+    0x8008_8048_0180_1004, 0x0810_8010_0080_2004, 0x0000_4010_0040_2000, 0x00C0_0480_2480_0056,
+    0x0001_2882_0004_1041, 0x4001_0001_0004_0200, 0x4A02_0080_8004_0002, 0x0205_0011_0004_0800,
+    0x1830_0800_8010_0082, 0x0090_0400_2008_0020, 0x0020_0020_8040_0081, 0x0440_1040_8000_2080,
+    0x0000_0200_0501_419C, 0x8010_0400_0102_1008, 0x0004_0040_0201_0040, 0x0050_0500_0801_0051,
+    0x2029_0300_2019_7000, 0x0144_1100_2000_4304, 0x0000_8480_4000_2000, 0x0102_9080_0140_0861,
+    0x2041_0008_5200_8100, 0x0006_0008_0200_0401, 0x0002_8014_0002_0080, 0x0400_8008_0004_0083,
+    0x0003_0021_01D0_0048, 0x0342_0040_8010_2200, 0x0028_8080_4000_2000, 0x0900_8020_4000_8000,
+    0x0200_0982_0024_0045, 0xC480_0100_0200_0A80, 0x0280_1102_0004_0080, 0x0100_0411_0002_0800,
+    0x4100_0820_0410_0101, 0x0880_0910_0080_2000, 0x0200_1100_8200_4020, 0x8080_0088_2010_4001,
+];
+
+const BISHOP_MAGICS : [u64; 64] = [
+    0x822A_0448_0819_4080, 0x2048_0883_3012_0200, 0x0100_0020_8401_2204, 0x0900_0010_1020_2200,
+    0x0000_0810_0104_0900, 0x2C80_8041_0080_9000, 0x8006_0024_8848_0802, 0x0402_0109_0110_0201,
+    0x0244_0102_0421_0201, 0x6011_0491_4404_0040, 0x8009_600A_4202_1408, 0x4010_0040_0501_0040,
+    0x4120_6086_4202_0800, 0x0000_0205_1088_0120, 0x0090_80A0_8820_0010, 0x5080_8084_1040_60C6,
+    0x0001_0806_0640_8080, 0x0008_0800_908A_0C20, 0x0350_0250_0408_0440, 0x0080_6801_0042_8400,
+    0x1040_8040_1040_0204, 0x1C02_0022_0800_0100, 0x0009_0410_4486_4260, 0x0602_0223_4002_2002,
+    0x021C_8082_0001_090B, 0x8090_4303_0002_2280, 0x2802_0401_1088_0800, 0x8004_1002_8014_0084,
+    0x0205_0200_8008_0080, 0x8020_8450_0001_0400, 0x0124_5008_0802_0200, 0x80C2_0820_0440_0241,
+    0x0448_8820_0201_0400, 0x9C00_8490_0304_1004, 0x0012_0080_0808_0140, 0x0060_8400_0080_2020,
+    0x1418_2800_0A82_0032, 0x2800_5009_2501_0201, 0x2288_0280_2810_A100, 0x6004_0624_1020_0801,
+    0x0545_0202_0042_0211, 0xA001_0000_6110_1004, 0x2001_0002_0082_0100, 0x8042_0004_0094_2402,
+    0x0401_0228_0411_0420, 0x2430_2018_0080_2208, 0x5820_8008_880B_1150, 0x0040_2004_5024_0D40,
+    0x0240_6200_9201_5000, 0x8408_0A00_9008_4885, 0x0110_0110_0804_1830, 0x80C2_9088_2000_0020,
+    0x0003_0820_4040_0300, 0x2002_8467_040B_0000, 0x04B6_2044_8080_8108, 0x0120_2002_4238_1112,
+    0x170A_0601_1412_0204, 0x0202_0090_4808_0101, 0x0018_C808_4004_1003, 0x0001_1040_0012_1840,
+    0x0144_0414_8002_0182, 0x0108_0081_0A01_0800, 0x081C_1004_A201_8440, 0x8008_121C_1802_0010,
+];
+
+const ROOK_DIRECTIONS : [(i32, i32); 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+const BISHOP_DIRECTIONS : [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+// The squares a king or knight on each square attacks
+pub(crate) const KING_ATTACKS : [u64; 64] = leaper_attacks(&KING_OFFSETS);
+pub(crate) const KNIGHT_ATTACKS : [u64; 64] = leaper_attacks(&KNIGHT_OFFSETS);
+// The squares a pawn of each color on each square attacks, indexed by color_index
+pub(crate) const PAWN_ATTACKS : [[u64; 64]; 2] = [leaper_attacks(&BLACK_PAWN_OFFSETS), leaper_attacks(&WHITE_PAWN_OFFSETS)];
+
+// Builds the attack table of a piece that jumps by fixed offsets, leaving out jumps off the board
+const fn leaper_attacks(offsets : &[(i32, i32)]) -> [u64; 64] {
+    let mut table = [0; 64];
+    let mut index = 0;
+    while index < 64 {
+        let mut offset = 0;
+        while offset < offsets.len() {
+            let x = (index % 8) as i32 + offsets[offset].0;
+            let y = (index / 8) as i32 + offsets[offset].1;
+            if 0 <= x && x < 8 && 0 <= y && y < 8 {
+                table[index] |= square_bit(x as usize, y as usize);
+            }
+            offset += 1;
+        }
+        index += 1;
+    }
+    return table
+}
+
+// Returns the squares the given piece attacks from a square, with the occupied squares given as a bitboard
+// Sliding pieces stop at the first occupied square in each direction, which is included since it may hold a piece to capture
+// Pawn attacks depend on the pawn's color, so pawns return no squares here and use pawn_attacks instead
+pub fn attacks(piece : Piece, square : Square, occupancy : u64) -> u64 {
+    let index = square.index();
+    match piece {
+        Piece::King => {KING_ATTACKS[index]},
+        Piece::Knight => {KNIGHT_ATTACKS[index]},
+        Piece::Rook => {rook_attacks(index, occupancy)},
+        Piece::Bishop => {bishop_attacks(index, occupancy)},
+        Piece::Queen => {rook_attacks(index, occupancy) | bishop_attacks(index, occupancy)},
+        Piece::Pawn => {0}
+    }
+}
+
+// Returns the squares a pawn of the given color attacks from a square
+pub fn pawn_attacks(color : Color, square : Square) -> u64 {
+    PAWN_ATTACKS[color_index(color)][square.index()]
+}
+
+// Returns the squares a rook on the square at index attacks, looked up in the magic tables
+pub(crate) fn rook_attacks(index : usize, occupancy : u64) -> u64 {
+    let tables = slider_tables();
+    tables.rook[index].lookup(&tables.attacks, occupancy)
+}
+
+// Returns the squares a bishop on the square at index attacks, looked up in the magic tables
+pub(crate) fn bishop_attacks(index : usize, occupancy : u64) -> u64 {
+    let tables = slider_tables();
+    tables.bishop[index].lookup(&tables.attacks, occupancy)
+}
+
+// Magic bitboard lookup for one slider on one square
+// The occupied squares that can block the slider are multiplied by the magic number, and the top bits of the
+// product index a table of attack sets that has no collisions between occupancies with different attacks
+#[derive(Clone, Copy, Default)]
+struct Magic {
+    mask: u64, // Squares whose occupancy matters, which leaves out the edge of the board at the end of each ray
+    magic: u64,
+    shift: u32, // 64 minus the number of index bits
+    offset: usize // Where this square's attack sets start in the shared table
+}
+
+impl Magic {
+    fn lookup(&self, attacks : &[u64], occupancy : u64) -> u64 {
+        attacks[self.offset + self.index(occupancy)]
+    }
+
+    fn index(&self, occupancy : u64) -> usize {
+        ((occupancy & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+// Magic lookups for rooks and bishops on every square, sharing one table of attack sets
+struct SliderTables {
+    rook: [Magic; 64],
+    bishop: [Magic; 64],
+    attacks: Vec<u64>
+}
+
+static SLIDER_TABLES : OnceLock<SliderTables> = OnceLock::new();
+
+// Returns the slider tables, filling them the first time they are needed
+fn slider_tables() -> &'static SliderTables {
+    SLIDER_TABLES.get_or_init(|| {
+        let mut attacks = Vec::new();
+        let mut rook = [Magic::default(); 64];
+        let mut bishop = [Magic::default(); 64];
+        for index in 0..64 {
+            rook[index] = fill_magic(index, ROOK_MAGICS[index], &ROOK_DIRECTIONS, &mut attacks);
+            bishop[index] = fill_magic(index, BISHOP_MAGICS[index], &BISHOP_DIRECTIONS, &mut attacks);
+        }
+        SliderTables { rook, bishop, attacks }
+    })
+}
+
+// Appends the attack sets of a slider moving in the given directions from the square at index to the shared table
+// Every occupancy of the relevant squares is walked once, so a magic number that mixes up two different attack
+// sets is caught here
+fn fill_magic(index : usize, magic : u64, directions : &[(i32, i32)], attacks : &mut Vec<u64>) -> Magic {
+    let mask = relevant_squares(index, directions);
+    let bits = mask.count_ones();
+    let entry = Magic { mask, magic, shift: 64 - bits, offset: attacks.len() };
+    let mut table = vec![None; 1 << bits];
+    // Go through every subset of the mask by rippling a carry through its bits
+    let mut occupancy : u64 = 0;
+    loop {
+        let attack = sliding_attacks(index, occupancy, directions);
+        let slot = &mut table[entry.index(occupancy)];
+        assert!(slot.is_none() || *slot == Some(attack), "Bad magic number for square {}", index);
+        *slot = Some(attack);
+        occupancy = occupancy.wrapping_sub(mask) & mask;
+        if occupancy == 0 {
+            break;
+        }
+    }
+    attacks.extend(table.into_iter().map(|attack| attack.unwrap_or(0)));
+    return entry
+}
+
+// Returns the squares on the rays from the square at index that can block a slider, which are all but the last
+// square of each ray since a piece there can not block anything behind it
+fn relevant_squares(index : usize, directions : &[(i32, i32)]) -> u64 {
+    let mut squares = 0;
+    for (step_x, step_y) in directions {
+        let (mut x, mut y) = ((index % 8) as i32 + step_x, (index / 8) as i32 + step_y);
+        while (0..8).contains(&(x + step_x)) && (0..8).contains(&(y + step_y)) {
+            squares |= square_bit(x as usize, y as usize);
+            x += step_x;
+            y += step_y;
+        }
+    }
+    return squares
+}
+
+// Returns the squares a piece sliding in the given directions from the square at index attacks by walking each ray
+// Each ray stops at the first occupied square, which is included since it may hold a piece to capture
+// Only used to fill the magic tables, which give the same result much faster
+fn sliding_attacks(index : usize, occupancy : u64, directions : &[(i32, i32)]) -> u64 {
+    let mut attacks = 0;
+    for (step_x, step_y) in directions {
+        let (mut x, mut y) = ((index % 8) as i32, (index / 8) as i32);
+        loop {
+            x += step_x;
+            y += step_y;
+            if !(0..8).contains(&x) || !(0..8).contains(&y) {
+                break;
+            }
+            let bit = square_bit(x as usize, y as usize);
+            attacks |= bit;
+            if occupancy & bit != 0 {
+                break;
+            }
+        }
+    }
+    return attacks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables() {
+        // A knight in the corner A8 reaches B6 and C7
+        assert!(KNIGHT_ATTACKS[0] == square_bit(1, 2) | square_bit(2, 1));
+        assert!(KING_ATTACKS[63].count_ones() == 3);
+        assert!(KING_ATTACKS[27].count_ones() == 8);
+        // Pawns on the A and H files only attack inwards
+        assert!(PAWN_ATTACKS[color_index(Color::White)][48] == square_bit(1, 5));
+        assert!(PAWN_ATTACKS[color_index(Color::Black)][15] == square_bit(6, 2));
+    }
+
+    #[test]
+    fn sliders() {
+        // A rook on D4 blocked by pieces on D6 and F4
+        let occupancy = square_bit(3, 2) | square_bit(5, 4);
+        let attacks = sliding_attacks(8 * 4 + 3, occupancy, &ROOK_DIRECTIONS);
+        assert!(attacks.count_ones() == 2 + 3 + 2 + 3);
+        assert!(attacks & square_bit(3, 2) != 0 && attacks & square_bit(3, 1) == 0);
+        assert!(squares(square_bit(0, 0) | square_bit(7, 7)).collect::<Vec<(usize, usize)>>() == [(0, 0), (7, 7)]);
+    }
+
+    // A xorshift64* pseudorandom number generator, for random occupancies
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+    }
+
+    #[test]
+    fn magic_lookup() {
+        // The magic tables agree with walking the rays, for any occupancy
+        let mut random = Random(1);
+        for index in 0..64 {
+            for _ in 0..200 {
+                let occupancy = random.next() & random.next();
+                assert!(rook_attacks(index, occupancy) == sliding_attacks(index, occupancy, &ROOK_DIRECTIONS));
+                assert!(bishop_attacks(index, occupancy) == sliding_attacks(index, occupancy, &BISHOP_DIRECTIONS));
+            }
+        }
+        // Only the four squares around a rook in the corner matter, and the edges never do
+        assert!(relevant_squares(0, &ROOK_DIRECTIONS).count_ones() == 12);
+        assert!(relevant_squares(27, &BISHOP_DIRECTIONS).count_ones() == 9);
+    }
+
+    #[test]
+    fn public_attacks() {
+        let d4 = "D4".parse::<Square>().unwrap();
+        let occupancy = d4.bit() | "D6".parse::<Square>().unwrap().bit();
+        assert!(attacks(Piece::Rook, d4, occupancy).count_ones() == 2 + 3 + 4 + 3);
+        assert!(attacks(Piece::Queen, d4, 0).count_ones() == 27);
+        assert!(attacks(Piece::Knight, d4, 0).count_ones() == 8);
+        assert!(attacks(Piece::Pawn, d4, 0) == 0);
+        assert!(pawn_attacks(Color::White, d4) == "C5".parse::<Square>().unwrap().bit() | "E5".parse::<Square>().unwrap().bit());
+        // In the starting position the rook on A1 only sees its neighbors
+        let game = crate::Game::new();
+        let a1 = "A1".parse::<Square>().unwrap();
+        assert!(game.get_bitboard(Piece::Rook, Color::White) & a1.bit() != 0);
+        assert!(attacks(Piece::Rook, a1, game.get_occupancy()) == "A2".parse::<Square>().unwrap().bit() | "B1".parse::<Square>().unwrap().bit());
+    }
+}
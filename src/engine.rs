@@ -0,0 +1,621 @@
+// A chess engine searching the moves of a Game for the best one
+// Scores are in centipawns from the point of view of the player to move, so higher is always better for that player
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::transposition::{Bound, TranspositionTable};
+use crate::{evaluate, piece_value, Game, Move, Piece};
+
+// Score of checkmating the opponent right away
+// Mates further away score one less per ply, so the quickest mate is preferred and the slowest defence chosen
+pub const MATE_SCORE : i32 = 100_000;
+
+// Any score at least this far from zero is a mate found by the search rather than an evaluation
+pub(crate) const MATE_THRESHOLD : i32 = MATE_SCORE - 1_000;
+
+// Higher than any score the search can return, used as the starting window
+const INFINITY : i32 = MATE_SCORE + 1;
+
+// Deepest iteration a search goes to when no limit stops it earlier
+pub const MAX_DEPTH : u32 = 64;
+
+// How many nodes the search visits between looks at the clock and the stop flag
+const CHECK_INTERVAL : u64 = 1024;
+
+// Time left on the clock that a search never uses, to cover the delay in getting the move played
+const CLOCK_MARGIN : Duration = Duration::from_millis(50);
+
+// How many moves the time left is shared between when there is no time control to reach
+const DEFAULT_MOVES_TO_GO : u32 = 30;
+
+// What a search found
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchResult {
+    pub best_move: Option<Move>, // None if the player to move has no legal moves or the game is over
+    pub score: i32, // The score of the best move, see MATE_SCORE for how mates are scored
+    pub principal_variation: Vec<Move>, // The best move followed by the best replies, as far as the search looked
+    pub depth: u32, // How many plies the search looked ahead
+    pub nodes: u64, // How many positions the search visited
+    pub elapsed: Duration // How long the search took
+}
+
+impl SearchResult {
+    // Returns how many moves of the player to move it takes to mate, negative if the player is getting mated
+    // Returns None if the score is not a mate score
+    pub fn mate_in(&self) -> Option<i32> {
+        if self.score >= MATE_THRESHOLD {
+            return Some((MATE_SCORE - self.score + 1) / 2);
+        }
+        if self.score <= -MATE_THRESHOLD {
+            return Some(-(MATE_SCORE + self.score) / 2);
+        }
+        return None
+    }
+
+    // Returns how many positions the search visited per second
+    pub fn nodes_per_second(&self) -> u64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds == 0.0 {
+            return 0;
+        }
+        return (self.nodes as f64 / seconds) as u64
+    }
+}
+
+// When a search started with Engine::search_with_limits stops
+// The first limit to be reached stops it, and with none set it goes on until the stop flag is raised
+// or MAX_DEPTH is reached
+// The first iteration always finishes, so the search has a move to return however soon it is stopped
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>, // Search for this long
+    pub time_left: Option<Duration>, // Time left on the clock of the player to move, of which the search takes a share
+    pub increment: Duration, // Time added to the clock after each move
+    pub moves_to_go: Option<u32>, // Moves left until the next time control adds time, if there is one
+    pub stop: Option<Arc<AtomicBool>> // Set to true from another thread to stop the search as soon as possible
+}
+
+impl SearchLimits {
+    // Returns limits that only stop the search after the given depth
+    pub fn depth(depth : u32) -> SearchLimits {
+        SearchLimits { depth: Some(depth), ..SearchLimits::default() }
+    }
+
+    // Returns how long the search may go on, and with a clock how long it may go on before it should not start
+    // another iteration, which would likely not finish in time
+    fn time_budget(&self) -> (Option<Duration>, Option<Duration>) {
+        let time_left = match self.time_left {
+            Some(time_left) => {time_left},
+            None => {return (self.movetime, None)}
+        };
+        let moves = self.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let share = (time_left / moves + self.increment).min(time_left.saturating_sub(CLOCK_MARGIN));
+        let hard = self.movetime.map_or(share, |movetime| movetime.min(share));
+        return (Some(hard), Some(hard / 2))
+    }
+}
+
+// Size of the transposition table of an Engine made with Engine::new, in megabytes
+pub const DEFAULT_TABLE_SIZE : usize = 16;
+
+// Searches positions for the best move with negamax alpha-beta
+// What it finds is kept in a transposition table between searches, which helps when searching the same game again
+#[derive(Clone, Debug)]
+pub struct Engine {
+    nodes: u64,
+    positions: Vec<u64>, // Repetition keys of the game so far followed by Zobrist keys of the positions being searched
+    table: TranspositionTable,
+    // The limits of the running search, which only apply once stoppable is set after the first iteration
+    node_limit: Option<u64>,
+    deadline: Option<Instant>,
+    stop: Option<Arc<AtomicBool>>,
+    stoppable: bool,
+    aborted: bool // Set once a limit is reached, after which every node returns right away
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
+
+impl Engine {
+    // Constructs an Engine with a transposition table of DEFAULT_TABLE_SIZE megabytes
+    pub fn new() -> Engine {
+        Engine::with_table_size(DEFAULT_TABLE_SIZE)
+    }
+
+    // Constructs an Engine with a transposition table of about the given number of megabytes
+    pub fn with_table_size(megabytes : usize) -> Engine {
+        Engine {
+            nodes: 0,
+            positions: Vec::new(),
+            table: TranspositionTable::new(megabytes),
+            node_limit: None,
+            deadline: None,
+            stop: None,
+            stoppable: false,
+            aborted: false
+        }
+    }
+
+    // Forgets every position searched so far, for starting on an unrelated game
+    pub fn clear_table(&mut self) -> () {
+        self.table.clear();
+    }
+
+    // Searches the position of the game the given number of plies deep and returns the best move found
+    // The game itself is not changed, the search works on a copy
+    // Every move is searched to the full depth and then through the captures that follow
+    // A depth of 0 is searched as 1, so there is a best move whenever the player to move has one
+    pub fn search(&mut self, game : &Game, depth : u32) -> SearchResult {
+        self.search_with_limits(game, &SearchLimits::depth(depth), |_| ())
+    }
+
+    // Searches the position of the game one ply deeper at a time until one of the limits is reached
+    // Calls progress with the result of every iteration that finished, and returns the last one
+    // Earlier iterations fill the transposition table with the best moves to try first, so going deeper one ply at a
+    // time costs little more than searching the final depth right away
+    pub fn search_with_limits(&mut self, game : &Game, limits : &SearchLimits, mut progress : impl FnMut(&SearchResult))
+            -> SearchResult {
+        let start = Instant::now();
+        let (time_allowed, iteration_time) = limits.time_budget();
+        self.nodes = 0;
+        self.positions = game.position_history.clone();
+        self.table.new_search();
+        self.node_limit = limits.nodes;
+        self.deadline = time_allowed.map(|time| start + time);
+        self.stop = limits.stop.clone();
+        self.stoppable = false;
+        self.aborted = false;
+        let mut game = game.clone();
+        let max_depth = limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH);
+        let mut depth = 1;
+        let mut result = self.search_depth(&mut game, depth, start);
+        progress(&result);
+        self.stoppable = true;
+        while depth < max_depth && result.best_move.is_some() {
+            if iteration_time.is_some_and(|time| start.elapsed() >= time) || self.out_of_time_or_stopped() {
+                break;
+            }
+            depth += 1;
+            let iteration = self.search_depth(&mut game, depth, start);
+            if self.aborted {
+                // An unfinished iteration may not have looked at the best move yet
+                result.nodes = iteration.nodes;
+                result.elapsed = iteration.elapsed;
+                break;
+            }
+            result = iteration;
+            progress(&result);
+        }
+        return result
+    }
+
+    // Searches the position to the given depth as one iteration of search_with_limits
+    fn search_depth(&mut self, game : &mut Game, depth : u32, start : Instant) -> SearchResult {
+        let mut principal_variation = Vec::new();
+        let score = if game.is_finished() {
+            // A finished game has no moves, but its score is still worth knowing for a checkmate
+            if game.is_in_check(game.player) && game.generate_moves().is_empty() {-MATE_SCORE} else {0}
+        }
+        else {
+            self.negamax(game, depth, 0, -INFINITY, INFINITY, &mut principal_variation)
+        };
+        return SearchResult {
+            best_move: principal_variation.first().copied(),
+            score,
+            principal_variation,
+            depth,
+            nodes: self.nodes,
+            elapsed: start.elapsed()
+        }
+    }
+
+    // Returns whether the search has to stop, because a limit was reached now or before
+    // The clock and the stop flag are only looked at every CHECK_INTERVAL nodes
+    fn should_stop(&mut self) -> bool {
+        if self.aborted || !self.stoppable {
+            return self.aborted;
+        }
+        if self.node_limit.is_some_and(|limit| self.nodes >= limit) {
+            self.aborted = true;
+        }
+        else if self.nodes.is_multiple_of(CHECK_INTERVAL) {
+            self.aborted = self.out_of_time_or_stopped();
+        }
+        return self.aborted
+    }
+
+    // Returns whether the time for the search is up or the stop flag was raised
+    fn out_of_time_or_stopped(&self) -> bool {
+        let out_of_time = self.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        let stopped = self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed));
+        return out_of_time || stopped
+    }
+
+    // Returns the score of the position for the player to move, searching depth more plies
+    // Scores at or below alpha only tell that the position is no better than that score, and scores at or above beta
+    // only that it is no worse, since the opponent would avoid it
+    // The best line found is written to principal_variation, which is left empty if no move scored above alpha
+    fn negamax(&mut self, game : &mut Game, depth : u32, ply : i32, mut alpha : i32, beta : i32,
+            principal_variation : &mut Vec<Move>) -> i32 {
+        self.nodes += 1;
+        principal_variation.clear();
+        if self.should_stop() {
+            return 0;
+        }
+        if ply > 0 && self.is_draw(game) {
+            return 0;
+        }
+        // Reuse an earlier search of this position if it looked at least as deep
+        // The root is always searched so there is a best move to return
+        let key = game.hash();
+        let entry = self.table.probe(key, ply);
+        if let Some(entry) = entry.filter(|entry| ply > 0 && entry.depth >= depth) {
+            let usable = match entry.bound {
+                Bound::Exact => {true},
+                Bound::Lower => {entry.score >= beta},
+                Bound::Upper => {entry.score <= alpha}
+            };
+            if usable {
+                principal_variation.extend(entry.best_move);
+                return entry.score;
+            }
+        }
+        let mut moves = game.generate_moves();
+        if moves.is_empty() {
+            if game.is_in_check(game.player) {
+                return -(MATE_SCORE - ply);
+            }
+            return 0
+        }
+        if depth == 0 {
+            return self.quiescence(game, alpha, beta, ply);
+        }
+        order_moves(game, &mut moves, entry.and_then(|entry| entry.best_move));
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        let mut line = Vec::new();
+        for chess_move in moves {
+            let entry = game.apply_move(chess_move);
+            self.positions.push(game.position_key());
+            let score = -self.negamax(game, depth - 1, ply + 1, -beta, -alpha, &mut line);
+            self.positions.pop();
+            game.revert_move(&entry);
+            // The score of an unfinished search means nothing, and must not get into the table
+            if self.aborted {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+            }
+            if score > alpha {
+                alpha = score;
+                best_move = Some(chess_move);
+                principal_variation.clear();
+                principal_variation.push(chess_move);
+                principal_variation.append(&mut line);
+                if score >= beta {
+                    break;
+                }
+            }
+        }
+        let bound = if best_score <= original_alpha {Bound::Upper} else if best_score >= beta {Bound::Lower} else {Bound::Exact};
+        self.table.store(key, depth, bound, best_score, best_move, ply);
+        return best_score
+    }
+
+    // Returns the score of the position once the captures available in it have played out, ply plies from the root
+    // The player to move can stand pat on the evaluation instead of capturing, since there is usually a quiet move
+    // at least as good, so only captures that could raise the score are searched
+    // A player in check has no quiet move to fall back on, so every way out of the check is searched instead
+    // Checks are not looked for, and promotions are only to a queen unless they get out of check
+    fn quiescence(&mut self, game : &mut Game, mut alpha : i32, beta : i32, ply : i32) -> i32 {
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+        let in_check = game.is_in_check(game.player);
+        let (stand_pat, mut moves) = if in_check {
+            let moves = game.generate_moves();
+            if moves.is_empty() {
+                return -(MATE_SCORE - ply);
+            }
+            (-INFINITY, moves)
+        }
+        else {
+            (evaluate(game), game.generate_captures())
+        };
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+        let mut best_score = stand_pat;
+        order_moves(game, &mut moves, None);
+        for chess_move in moves {
+            if !in_check {
+                if chess_move.promotion.is_some_and(|piece| piece != Piece::Queen) {
+                    continue;
+                }
+                // Delta pruning: skip captures that could not reach alpha even if the capturing piece were never lost
+                if stand_pat + capture_gain(game, chess_move) + DELTA_MARGIN <= alpha {
+                    continue;
+                }
+            }
+            let entry = game.apply_move(chess_move);
+            let score = -self.quiescence(game, -beta, -alpha, ply + 1);
+            game.revert_move(&entry);
+            if self.aborted {
+                return 0;
+            }
+            best_score = best_score.max(score);
+            if score > alpha {
+                alpha = score;
+                if score >= beta {
+                    break;
+                }
+            }
+        }
+        return best_score
+    }
+
+    // Returns whether the position is drawn by the fifty-move rule or has occurred before
+    // A position that repeats once is scored as a draw, since whatever led back to it could be repeated again
+    fn is_draw(&self, game : &Game) -> bool {
+        if game.halfmove_clock >= 100 {
+            return true;
+        }
+        // Only positions since the last pawn move or capture can repeat
+        let current = self.positions.len() - 1;
+        let earliest = current.saturating_sub(game.halfmove_clock as usize);
+        return self.positions[earliest..current].contains(&self.positions[current])
+    }
+}
+
+// How much more than the material it wins a capture is allowed to gain before delta pruning gives up on it
+// Covers the change in the square bonuses, which is rarely more than this
+const DELTA_MARGIN : i32 = 200;
+
+// Returns the material a capture or promotion wins, not counting any reply
+fn capture_gain(game : &Game, chess_move : Move) -> i32 {
+    let mut gain = 0;
+    if chess_move.flags.en_passant {
+        gain += piece_value(Piece::Pawn);
+    }
+    else if let Some(piece) = game.board[chess_move.to.1][chess_move.to.0] {
+        gain += piece_value(piece);
+    }
+    if let Some(piece) = chess_move.promotion {
+        gain += piece_value(piece) - piece_value(Piece::Pawn);
+    }
+    return gain
+}
+
+// Sorts moves so the ones most likely to be good are searched first, which lets alpha-beta skip more of the rest
+// The best move from an earlier search of the position comes first, then promotions and captures of valuable pieces
+// by cheap ones, and the other moves keep their order
+fn order_moves(game : &Game, moves : &mut [Move], best_move : Option<Move>) -> () {
+    let score = |chess_move : &Move| {
+        if Some(*chess_move) == best_move {
+            return 100_000;
+        }
+        let mut score = 0;
+        if chess_move.flags.capture {
+            let (from, to) = (chess_move.from, chess_move.to);
+            let victim = if chess_move.flags.en_passant {Piece::Pawn} else {game.board[to.1][to.0].unwrap()};
+            let attacker = game.board[from.1][from.0].unwrap();
+            score += 10 * piece_value(victim) - piece_value(attacker) + 10_000;
+        }
+        if let Some(piece) = chess_move.promotion {
+            score += piece_value(piece) * 10;
+        }
+        return score
+    };
+    moves.sort_by_key(|chess_move| -score(chess_move));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GameState, START_FEN};
+
+    // Plain negamax without pruning, which alpha-beta has to agree with
+    // Leaves get a quiescence search with a full window, so only the pruning above them differs
+    fn minimax(game : &mut Game, depth : u32, ply : i32) -> i32 {
+        let moves = game.generate_moves();
+        if moves.is_empty() {
+            return if game.is_in_check(game.player) {-(MATE_SCORE - ply)} else {0};
+        }
+        if depth == 0 {
+            return Engine::with_table_size(0).quiescence(game, -INFINITY, INFINITY, ply);
+        }
+        let mut best = -INFINITY;
+        for chess_move in moves {
+            let entry = game.apply_move(chess_move);
+            best = best.max(-minimax(game, depth - 1, ply + 1));
+            game.revert_move(&entry);
+        }
+        return best
+    }
+
+    #[test]
+    fn same_as_minimax() {
+        // Kiwipete has so many moves and captures that plain negamax is slow past depth 1
+        let positions = [(START_FEN, 3), ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 1),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3), ("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", 3)];
+        let mut engine = Engine::new();
+        for (fen, max_depth) in positions {
+            let mut game = Game::from_fen(fen).unwrap();
+            for depth in 1..=max_depth {
+                let result = engine.search(&game, depth);
+                assert!(result.score == minimax(&mut game, depth, 0), "{} at depth {}", fen, depth);
+            }
+        }
+    }
+
+    #[test]
+    fn finds_mate() {
+        // Back rank mate in one
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let result = Engine::new().search(&game, 3);
+        assert!(result.best_move == Some(Move::from_uci("a1a8").unwrap()));
+        assert!(result.score == MATE_SCORE - 1);
+        assert!(result.mate_in() == Some(1));
+
+        // Mate in two, starting with a quiet king move that a material count alone would not favor
+        let game = Game::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+        let result = Engine::new().search(&game, 3);
+        assert!(result.mate_in() == Some(2));
+        assert!(result.principal_variation.len() == 3);
+        // The line ends in checkmate when played out
+        let mut played = game.clone();
+        for chess_move in &result.principal_variation {
+            assert!(played.play(*chess_move).is_ok());
+        }
+        assert!(played.get_game_state() == GameState::Checkmate);
+
+        // The side getting mated sees it coming
+        let game = Game::from_fen("k7/8/1K6/8/8/8/8/7R b - - 0 1").unwrap();
+        let result = Engine::new().search(&game, 4);
+        assert!(result.mate_in() == Some(-1));
+    }
+
+    #[test]
+    fn principal_variation() {
+        // Winning the queen left hanging
+        let game = Game::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let result = Engine::new().search(&game, 2);
+        assert!(result.best_move == Some(Move::from_uci("d2d5").unwrap()));
+        assert!(result.principal_variation.len() == 2);
+        assert!(result.depth == 2 && result.nodes > 0);
+        let mut played = game.clone();
+        for chess_move in &result.principal_variation {
+            assert!(played.play(*chess_move).is_ok());
+        }
+        // The score is the evaluation at the end of the line, with white to move again
+        assert!(result.score == evaluate(&played));
+    }
+
+    #[test]
+    fn quiescence() {
+        // Taking the defended pawn loses the queen one ply past the search depth
+        let game = Game::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let result = Engine::new().search(&game, 1);
+        assert!(result.best_move != Some(Move::from_uci("d1d5").unwrap()));
+        assert!(result.score > piece_value(Piece::Queen) - 3 * piece_value(Piece::Pawn));
+        // Standing pat keeps the score of a quiet position at its evaluation
+        let mut game = Game::new();
+        assert!(Engine::new().quiescence(&mut game, -INFINITY, INFINITY, 0) == evaluate(&game));
+        // A free pawn is taken, and the score is that of the position after taking it
+        let mut game = Game::from_fen("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let score = Engine::new().quiescence(&mut game, -INFINITY, INFINITY, 0);
+        let mut after = game.clone();
+        assert!(after.make_san_move("exd5").is_ok());
+        assert!(score == -evaluate(&after) && score > evaluate(&game));
+        // A player in check can not stand pat, so a checkmate scores as one
+        let mut game = Game::from_fen("R5k1/5ppp/8/8/8/8/5PPP/6K1 b - - 0 1").unwrap();
+        assert!(Engine::new().quiescence(&mut game, -INFINITY, INFINITY, 3) == -(MATE_SCORE - 3));
+        // And has to get out of check even when every way out loses material, here the queen to a knight fork
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/2n5/Q3K3 w - - 0 1").unwrap();
+        assert!(evaluate(&game) > 0 && Engine::new().quiescence(&mut game, -INFINITY, INFINITY, 0) < 0);
+    }
+
+    #[test]
+    fn transpositions() {
+        // A second search of the same position reuses the first, and a bigger table never changes the result
+        let game = Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut engine = Engine::with_table_size(1);
+        let first = engine.search(&game, 3);
+        let second = engine.search(&game, 3);
+        assert!(second.nodes < first.nodes);
+        assert!(second.score == first.score && second.best_move == first.best_move);
+        let mut without_table = Engine::with_table_size(0);
+        assert!(without_table.search(&game, 3).score == first.score);
+        engine.clear_table();
+        assert!(engine.search(&game, 3).nodes == first.nodes);
+    }
+
+    #[test]
+    fn iterative_deepening() {
+        let game = Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut iterations = Vec::new();
+        let result = Engine::new().search_with_limits(&game, &SearchLimits::depth(3), |result| iterations.push(result.clone()));
+        assert!(iterations.iter().map(|iteration| iteration.depth).collect::<Vec<u32>>() == [1, 2, 3]);
+        assert!(iterations.windows(2).all(|pair| pair[0].nodes < pair[1].nodes));
+        assert!(iterations.iter().all(|iteration| iteration.principal_variation.len() as u32 <= iteration.depth));
+        assert!(*iterations.last().unwrap() == result);
+        assert!(result.nodes_per_second() > 0);
+        // The same depth in one go gives the same score
+        assert!(Engine::new().search(&game, 3).score == result.score);
+    }
+
+    #[test]
+    fn limits() {
+        let game = Game::new();
+        // Only the first iteration is finished when the node limit is very low
+        let limits = SearchLimits { nodes: Some(10), ..SearchLimits::default() };
+        let result = Engine::new().search_with_limits(&game, &limits, |_| ());
+        assert!(result.depth == 1 && result.best_move.is_some());
+        // The second iteration stops at its first node
+        assert!(result.nodes == Engine::new().search(&game, 1).nodes + 1);
+        let limits = SearchLimits { nodes: Some(5_000), ..SearchLimits::default() };
+        let result = Engine::new().search_with_limits(&game, &limits, |_| ());
+        assert!(result.nodes == 5_000 && result.depth > 1);
+
+        // The first iteration is finished even with no time to search, and no deeper one is started
+        let limits = SearchLimits { movetime: Some(Duration::ZERO), ..SearchLimits::default() };
+        let result = Engine::new().search_with_limits(&game, &limits, |_| ());
+        assert!(result.depth == 1 && result.best_move.is_some());
+        // A depth of 0 is searched as 1
+        let result = Engine::new().search(&game, 0);
+        assert!(result.depth == 1 && result.best_move.is_some());
+
+        // A 30th of the clock and most of the increment
+        let limits = SearchLimits { time_left: Some(Duration::from_secs(3)), increment: Duration::from_millis(100), ..SearchLimits::default() };
+        assert!(limits.time_budget() == (Some(Duration::from_millis(200)), Some(Duration::from_millis(100))));
+        // Never more than is left on the clock
+        let limits = SearchLimits { time_left: Some(Duration::from_millis(60)), increment: Duration::from_secs(1), moves_to_go: Some(1), ..SearchLimits::default() };
+        assert!(limits.time_budget().0 == Some(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn stop_flag() {
+        // A search stopped before it starts still finishes its first iteration
+        let limits = SearchLimits { stop: Some(Arc::new(AtomicBool::new(true))), ..SearchLimits::default() };
+        let result = Engine::new().search_with_limits(&Game::new(), &limits, |_| ());
+        assert!(result.depth == 1 && result.best_move.is_some());
+        // Raising the flag between iterations keeps the next one from starting
+        let stop = Arc::new(AtomicBool::new(false));
+        let limits = SearchLimits { stop: Some(stop.clone()), ..SearchLimits::default() };
+        let result = Engine::new().search_with_limits(&Game::new(), &limits, |result| {
+            if result.depth == 2 {
+                stop.store(true, Ordering::Relaxed);
+            }
+        });
+        assert!(result.depth == 2 && result.best_move.is_some());
+    }
+
+    #[test]
+    fn no_moves() {
+        // Stalemate and checkmate have no best move
+        let stalemate = Game::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let result = Engine::new().search(&stalemate, 3);
+        assert!(result.best_move.is_none() && result.score == 0);
+        let checkmate = Game::from_fen("R5k1/5ppp/8/8/8/8/5PPP/6K1 b - - 0 1").unwrap();
+        let result = Engine::new().search(&checkmate, 3);
+        assert!(result.best_move.is_none() && result.score == -MATE_SCORE);
+        // A draw by repetition is worth nothing even when ahead in material
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1").unwrap();
+        for san in ["Kd1", "Kd8", "Ke1", "Ke8", "Kd1", "Kd8", "Ke1", "Ke8"] {
+            assert!(game.make_san_move(san).is_ok());
+        }
+        assert!(game.claim_draw().is_some());
+        assert!(Engine::new().search(&game, 2).best_move.is_none());
+    }
+}
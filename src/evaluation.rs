@@ -0,0 +1,221 @@
+// Static evaluation, which rates a position without looking at any moves
+// Scores are in centipawns, a hundredth of a pawn
+
+use crate::bitboard::{piece_index, squares};
+use crate::{Color, Game, Piece};
+
+// Values of each piece in centipawns, indexed by piece_index
+// The king can never be captured, so it is worth nothing towards the material balance
+const PIECE_VALUES : [i32; 6] = [0, 900, 500, 330, 320, 100];
+
+// How much each piece counts towards the game phase, indexed by piece_index
+// The starting position adds up to MIDDLEGAME_PHASE, and the score moves towards the endgame tables as pieces come off
+const PHASE_WEIGHTS : [i32; 6] = [0, 4, 2, 1, 1, 0];
+const MIDDLEGAME_PHASE : i32 = 24;
+
+// Bonuses for standing on each square, from white's side of the board
+// Laid out like the board with A8 first, so black pieces look their square up mirrored top to bottom
+const PAWN_MIDDLEGAME : [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     10,  10,  20,  30,  30,  20,  10,  10,
+      5,   5,  10,  25,  25,  10,   5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0
+];
+
+// Passed pawns decide endgames, so the closer to promotion the better
+const PAWN_ENDGAME : [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     80,  80,  80,  80,  80,  80,  80,  80,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     30,  30,  30,  30,  30,  30,  30,  30,
+     15,  15,  15,  15,  15,  15,  15,  15,
+      5,   5,   5,   5,   5,   5,   5,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0
+];
+
+const KNIGHT : [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50
+];
+
+const BISHOP : [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20
+];
+
+const ROOK : [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0
+];
+
+const QUEEN : [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20
+];
+
+// The king hides behind its pawns while there are pieces around to attack it
+const KING_MIDDLEGAME : [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20
+];
+
+// And comes to the center once they are gone
+const KING_ENDGAME : [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50
+];
+
+// Square tables for each piece, indexed by piece_index
+const MIDDLEGAME_TABLES : [&[i32; 64]; 6] = [&KING_MIDDLEGAME, &QUEEN, &ROOK, &BISHOP, &KNIGHT, &PAWN_MIDDLEGAME];
+const ENDGAME_TABLES : [&[i32; 64]; 6] = [&KING_ENDGAME, &QUEEN, &ROOK, &BISHOP, &KNIGHT, &PAWN_ENDGAME];
+
+// The parts a position's score is made of
+// Every part is from white's point of view, positive when white is better, except for score
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Evaluation {
+    pub material: i32, // Value of white's pieces minus black's
+    pub middlegame_squares: i32, // Square bonuses of white's pieces minus black's, by the middlegame tables
+    pub endgame_squares: i32, // The same by the endgame tables
+    pub phase: i32, // From MIDDLEGAME_PHASE (24) with all pieces on the board down to 0 with only kings and pawns
+    pub squares: i32, // The square bonuses blended by phase, mostly the middlegame ones early and the endgame ones late
+    pub total: i32, // Material plus the blended square bonuses
+    pub score: i32 // The total from the point of view of the player to move, as returned by evaluate
+}
+
+// Returns the value of a piece in centipawns, 0 for the king
+pub fn piece_value(piece : Piece) -> i32 {
+    PIECE_VALUES[piece_index(piece)]
+}
+
+// Returns the score of the position in centipawns from the point of view of the player to move
+// Takes material and where each piece stands into account, see evaluate_breakdown for the parts
+pub fn evaluate(game : &Game) -> i32 {
+    evaluate_breakdown(game).score
+}
+
+// Returns the score of the position along with the parts it is made of
+pub fn evaluate_breakdown(game : &Game) -> Evaluation {
+    let mut evaluation = Evaluation::default();
+    for piece in [Piece::King, Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight, Piece::Pawn] {
+        let index = piece_index(piece);
+        for (color, sign) in [(Color::White, 1), (Color::Black, -1)] {
+            for (x, y) in squares(game.get_bitboard(piece, color)) {
+                // Black's pieces see the board from the other side
+                let square = if color == Color::White {8 * y + x} else {8 * (7 - y) + x};
+                evaluation.material += sign * PIECE_VALUES[index];
+                evaluation.middlegame_squares += sign * MIDDLEGAME_TABLES[index][square];
+                evaluation.endgame_squares += sign * ENDGAME_TABLES[index][square];
+                evaluation.phase += PHASE_WEIGHTS[index];
+            }
+        }
+    }
+    // Promotions can take the phase past the starting position
+    evaluation.phase = evaluation.phase.min(MIDDLEGAME_PHASE);
+    evaluation.squares = (evaluation.middlegame_squares * evaluation.phase
+        + evaluation.endgame_squares * (MIDDLEGAME_PHASE - evaluation.phase)) / MIDDLEGAME_PHASE;
+    evaluation.total = evaluation.material + evaluation.squares;
+    evaluation.score = if game.get_player() == Color::White {evaluation.total} else {-evaluation.total};
+    return evaluation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::START_FEN;
+
+    // Returns the FEN of the same position with the colors swapped and the board turned upside down
+    fn mirror(fen : &str) -> String {
+        let fields = fen.split(' ').collect::<Vec<&str>>();
+        let swap_case = |text : &str| text.chars()
+            .map(|c| if c.is_ascii_uppercase() {c.to_ascii_lowercase()} else {c.to_ascii_uppercase()})
+            .collect::<String>();
+        let placement = fields[0].split('/').rev().map(swap_case).collect::<Vec<String>>().join("/");
+        let player = if fields[1] == "w" {"b"} else {"w"};
+        let en_passant = fields[3].chars().map(|c| match c {'3' => {'6'}, '6' => {'3'}, _ => {c}}).collect::<String>();
+        return format!("{} {} {} {} {} {}", placement, player, swap_case(fields[2]), en_passant, fields[4], fields[5])
+    }
+
+    #[test]
+    fn symmetry() {
+        assert!(evaluate(&Game::new()) == 0);
+        for fen in [START_FEN, "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8"] {
+            let game = Game::from_fen(fen).unwrap();
+            let mirrored = Game::from_fen(&mirror(fen)).unwrap();
+            let (evaluation, mirrored_evaluation) = (evaluate_breakdown(&game), evaluate_breakdown(&mirrored));
+            assert!(evaluation.score == mirrored_evaluation.score, "{}", fen);
+            assert!(evaluation.total == -mirrored_evaluation.total, "{}", fen);
+            assert!(evaluation.phase == mirrored_evaluation.phase, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn breakdown() {
+        // White is a knight up, developed to the center, with black to move
+        let game = Game::from_fen("rnbqkb1r/pppppppp/8/8/4N3/8/PPPPPPPP/RNBQKB1R b KQkq - 0 1").unwrap();
+        let evaluation = evaluate_breakdown(&game);
+        assert!(evaluation.material == piece_value(Piece::Knight));
+        assert!(evaluation.phase == 23);
+        // The other knights stand on matching squares, so only the extra one on a 20 square counts
+        assert!(evaluation.middlegame_squares == 20 && evaluation.endgame_squares == 20);
+        assert!(evaluation.total == evaluation.material + evaluation.squares);
+        assert!(evaluation.score == -evaluation.total);
+        assert!(evaluate(&game) == evaluation.score);
+        assert!(piece_value(Piece::King) == 0 && piece_value(Piece::Queen) == 900);
+    }
+
+    #[test]
+    fn tapering() {
+        // With only kings and pawns left the endgame tables decide, so a central king is better than one in the corner
+        let central = evaluate_breakdown(&Game::from_fen("8/8/8/3k4/8/8/8/K7 w - - 0 1").unwrap());
+        assert!(central.phase == 0);
+        assert!(central.squares == central.endgame_squares && central.squares == -50 - 40);
+        // With all pieces on the board the king belongs behind its pawns
+        let start = evaluate_breakdown(&Game::new());
+        assert!(start.phase == MIDDLEGAME_PHASE && start.squares == start.middlegame_squares);
+        // An advanced pawn counts for more in the endgame
+        let pawn = evaluate_breakdown(&Game::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap());
+        assert!(pawn.endgame_squares > pawn.middlegame_squares);
+    }
+}
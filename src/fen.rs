@@ -0,0 +1,392 @@
+use std::fmt;
+
+use crate::{get_opposite_color, CastlingRights, Color, Game, Piece};
+
+// Forsyth-Edwards Notation of the standard starting position
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// Reasons a FEN string could not be turned into a Game
+// Ranks are given by their number, 8 being the top of the board
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FenError {
+    WrongFieldCount(usize), // The string did not have 4 or 6 space separated fields
+    WrongRankCount(usize), // The piece placement did not have 8 ranks separated by '/'
+    WrongRankLength(usize), // The given rank did not describe exactly 8 squares
+    ConsecutiveDigits(usize), // The given rank had two digits in a row, like "35" instead of "8"
+    InvalidPiece(char), // A character in the piece placement was neither a piece nor a digit 1-8
+    InvalidSideToMove(String), // The side to move was not "w" or "b"
+    InvalidCastling(String), // Castling availability was not "-" or a combination of "KQkq" with the king and rook of each right on their home squares
+    InvalidEnPassant(String), // The en passant square was not "-" or an empty square behind an enemy pawn that just moved two steps from its empty starting square
+    InvalidHalfmoveClock(String), // The halfmove clock was not a non-negative number
+    InvalidFullmoveNumber(String), // The fullmove number was not a positive number
+    WrongKingCount(Color), // The given player does not have exactly one king
+    PawnOnBackRank, // A pawn stands on the first or eighth rank
+    OpponentInCheck // The player who is not to move is in check, which can not happen in a legal game
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::WrongFieldCount(count) => {write!(f, "expected 4 or 6 fields, found {}", count)},
+            FenError::WrongRankCount(count) => {write!(f, "expected 8 ranks, found {}", count)},
+            FenError::WrongRankLength(rank) => {write!(f, "rank {} does not describe exactly 8 squares", rank)},
+            FenError::ConsecutiveDigits(rank) => {write!(f, "rank {} has two digits in a row", rank)},
+            FenError::InvalidPiece(character) => {write!(f, "invalid piece '{}'", character)},
+            FenError::InvalidSideToMove(field) => {write!(f, "invalid side to move \"{}\"", field)},
+            FenError::InvalidCastling(field) => {write!(f, "invalid castling availability \"{}\"", field)},
+            FenError::InvalidEnPassant(field) => {write!(f, "invalid en passant square \"{}\"", field)},
+            FenError::InvalidHalfmoveClock(field) => {write!(f, "invalid halfmove clock \"{}\"", field)},
+            FenError::InvalidFullmoveNumber(field) => {write!(f, "invalid fullmove number \"{}\"", field)},
+            FenError::WrongKingCount(color) => {write!(f, "{:?} does not have exactly one king", color)},
+            FenError::PawnOnBackRank => {write!(f, "pawn on the first or eighth rank")},
+            FenError::OpponentInCheck => {write!(f, "the player not to move is in check")}
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+impl Game {
+    // Constructs a Game from a position in Forsyth-Edwards Notation
+    // The halfmove clock and fullmove number may be left out, in which case they default to 0 and 1
+    // The game state is set from the position, so a FEN of a finished game gives a finished Game
+    pub fn from_fen(fen: &str) -> Result<Game, FenError> {
+        let fields = fen.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() != 4 && fields.len() != 6 {
+            return Err(FenError::WrongFieldCount(fields.len()));
+        }
+        let mut game = Game::new();
+        game.board = [[None; 8]; 8];
+        game.black = 0;
+        game.white = 0;
+        game.pieces = [0; 6];
+
+        // Piece placement, starting from the top left corner
+        let ranks = fields[0].split('/').collect::<Vec<&str>>();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongRankCount(ranks.len()));
+        }
+        for (y, rank) in ranks.into_iter().enumerate() {
+            let mut x = 0;
+            let mut after_digit = false;
+            for character in rank.chars() {
+                if x >= 8 {
+                    return Err(FenError::WrongRankLength(8 - y));
+                }
+                match character {
+                    '1'..='8' => {
+                        // Empty squares in a row are always counted by a single digit
+                        if after_digit {
+                            return Err(FenError::ConsecutiveDigits(8 - y));
+                        }
+                        after_digit = true;
+                        x += character.to_digit(10).unwrap() as usize;
+                    },
+                    _ => {
+                        after_digit = false;
+                        let (piece, color) = char_to_piece(character).ok_or(FenError::InvalidPiece(character))?;
+                        game.place_piece(x, y, piece, color);
+                        x += 1;
+                    }
+                }
+            }
+            if x != 8 {
+                return Err(FenError::WrongRankLength(8 - y));
+            }
+        }
+
+        game.player = match fields[1] {
+            "w" => {Color::White},
+            "b" => {Color::Black},
+            _ => {return Err(FenError::InvalidSideToMove(fields[1].to_string()))}
+        };
+
+        game.castling = parse_castling(fields[2], &game).ok_or(FenError::InvalidCastling(fields[2].to_string()))?;
+
+        game.en_passant = match fields[3] {
+            "-" => {None},
+            _ => {
+                let target = parse_en_passant(fields[3], &game)
+                    .ok_or(FenError::InvalidEnPassant(fields[3].to_string()))?;
+                Some(target)
+            }
+        };
+
+        if fields.len() == 6 {
+            game.halfmove_clock = fields[4].parse::<u32>()
+                .map_err(|_| FenError::InvalidHalfmoveClock(fields[4].to_string()))?;
+            game.fullmove_number = match fields[5].parse::<u32>() {
+                Ok(number) if number > 0 => {number},
+                _ => {return Err(FenError::InvalidFullmoveNumber(fields[5].to_string()))}
+            };
+        }
+        else {
+            game.halfmove_clock = 0;
+            game.fullmove_number = 1;
+        }
+
+        // Reject positions the rest of the game logic can not handle
+        for color in [Color::White, Color::Black] {
+            let kings = (0..64)
+                .filter(|index| game.board[index / 8][index % 8] == Some(Piece::King)
+                    && game.get_color_at(index % 8, index / 8) == Some(color))
+                .count();
+            if kings != 1 {
+                return Err(FenError::WrongKingCount(color));
+            }
+        }
+        if game.board[0].contains(&Some(Piece::Pawn)) || game.board[7].contains(&Some(Piece::Pawn)) {
+            return Err(FenError::PawnOnBackRank);
+        }
+        if game.is_in_check(get_opposite_color(game.player)) {
+            return Err(FenError::OpponentInCheck);
+        }
+
+        game.hash = game.compute_hash();
+        let position = game.position_key();
+        game.position_history = vec![position];
+        game.start_fen = game.to_fen();
+        game.update_state();
+        return Ok(game)
+    }
+
+    // Returns the current position in Forsyth-Edwards Notation
+    pub fn to_fen(&self) -> String {
+        let placement = (0..8).map(|y| {
+            let mut rank = String::new();
+            let mut empty = 0;
+            for x in 0..8 {
+                match self.board[y][x] {
+                    Some(piece) => {
+                        if empty > 0 {
+                            rank.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        rank.push(piece_to_char(piece, self.get_color_at(x, y).unwrap()));
+                    },
+                    None => {empty += 1}
+                }
+            }
+            if empty > 0 {
+                rank.push_str(&empty.to_string());
+            }
+            rank
+        })
+        .collect::<Vec<String>>()
+        .join("/");
+
+        let player = match self.player {
+            Color::White => {"w"},
+            Color::Black => {"b"}
+        };
+
+        let mut castling = String::new();
+        for (allowed, character) in [
+            (self.castling.white_king_side, 'K'),
+            (self.castling.white_queen_side, 'Q'),
+            (self.castling.black_king_side, 'k'),
+            (self.castling.black_queen_side, 'q')
+        ] {
+            if allowed {
+                castling.push(character);
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        let en_passant = match self.get_en_passant_square() {
+            Some(square) => {square.to_ascii_lowercase()},
+            None => {String::from("-")}
+        };
+
+        format!("{} {} {} {} {} {}", placement, player, castling, en_passant, self.halfmove_clock, self.fullmove_number)
+    }
+}
+
+// Returns the FEN letter of a piece, uppercase for white and lowercase for black
+fn piece_to_char(piece : Piece, color : Color) -> char {
+    let character = match piece {
+        Piece::King => {'K'},
+        Piece::Queen => {'Q'},
+        Piece::Rook => {'R'},
+        Piece::Bishop => {'B'},
+        Piece::Knight => {'N'},
+        Piece::Pawn => {'P'}
+    };
+    match color {
+        Color::White => {character},
+        Color::Black => {character.to_ascii_lowercase()}
+    }
+}
+
+// Returns the piece and color of a FEN letter, or None if the letter is not a piece
+fn char_to_piece(character : char) -> Option<(Piece, Color)> {
+    let piece = match character.to_ascii_uppercase() {
+        'K' => {Piece::King},
+        'Q' => {Piece::Queen},
+        'R' => {Piece::Rook},
+        'B' => {Piece::Bishop},
+        'N' => {Piece::Knight},
+        'P' => {Piece::Pawn},
+        _ => {return None}
+    };
+    let color = if character.is_ascii_uppercase() {Color::White} else {Color::Black};
+    return Some((piece, color))
+}
+
+// Parses the castling availability field, rejecting unknown and repeated letters
+// Each right needs the king and the rook it castles with on their home squares, or it could never be used
+fn parse_castling(field : &str, game : &Game) -> Option<CastlingRights> {
+    let mut rights = CastlingRights {
+        white_king_side: false,
+        white_queen_side: false,
+        black_king_side: false,
+        black_queen_side: false
+    };
+    if field == "-" {
+        return Some(rights);
+    }
+    for character in field.chars() {
+        // The right, its color's home rank and the file of its rook
+        let (right, y, rook_x) = match character {
+            'K' => {(&mut rights.white_king_side, 7, 7)},
+            'Q' => {(&mut rights.white_queen_side, 7, 0)},
+            'k' => {(&mut rights.black_king_side, 0, 7)},
+            'q' => {(&mut rights.black_queen_side, 0, 0)},
+            _ => {return None}
+        };
+        let color = if character.is_ascii_uppercase() {Color::White} else {Color::Black};
+        let is_home = |x : usize, piece : Piece| game.board[y][x] == Some(piece) && game.get_color_at(x, y) == Some(color);
+        if *right || !is_home(4, Piece::King) || !is_home(rook_x, Piece::Rook) {
+            return None;
+        }
+        *right = true;
+    }
+    return Some(rights)
+}
+
+// Parses the en passant field into numerical coordinates
+// The square must be on the sixth rank when white is to move and on the third rank when black is to move
+fn parse_en_passant(field : &str, game : &Game) -> Option<(usize, usize)> {
+    let characters = field.chars().collect::<Vec<char>>();
+    if characters.len() != 2 || !('a'..='h').contains(&characters[0]) {
+        return None;
+    }
+    let x = characters[0] as usize - 'a' as usize;
+    // The rank of the target square, the rank the pawn moved to and the rank it came from
+    let (y, pawn_y, origin_y) = match (characters[1], game.player) {
+        ('6', Color::White) => {(2, 3, 1)},
+        ('3', Color::Black) => {(5, 4, 6)},
+        _ => {return None}
+    };
+    // The pawn that just moved belongs to the player who is not to move, and passed over an empty square
+    let opponent = get_opposite_color(game.player);
+    if game.board[pawn_y][x] != Some(Piece::Pawn) || game.get_color_at(x, pawn_y) != Some(opponent)
+        || game.board[y][x].is_some() || game.board[origin_y][x].is_some() {
+        return None;
+    }
+    return Some((x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DrawReason, GameState};
+
+    #[test]
+    fn start_position() {
+        let game = Game::new();
+        assert!(game.to_fen() == START_FEN);
+        let game = Game::from_fen(START_FEN).unwrap();
+        assert!(game.get_board() == Game::new().get_board());
+        assert!(game.get_player() == Color::White);
+        assert!(game.get_game_state() == GameState::InProgress);
+    }
+
+    #[test]
+    fn round_trip() {
+        for fen in [
+            START_FEN,
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            "4k3/8/8/8/8/8/8/4K2R b K - 37 64"
+        ] {
+            let game = Game::from_fen(fen).unwrap();
+            println!("{}", game);
+            assert!(game.to_fen() == fen);
+        }
+    }
+
+    #[test]
+    fn played_moves() {
+        let mut game = Game::new();
+        assert!(game.make_move("E2", "E4").is_ok());
+        assert!(game.to_fen() == "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        assert!(game.make_move("C7", "C5").is_ok());
+        assert!(game.to_fen() == "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2");
+        assert!(game.make_move("G1", "F3").is_ok());
+        assert!(game.to_fen() == "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+    }
+
+    #[test]
+    fn optional_counters() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - -").unwrap();
+        assert!(game.get_halfmove_clock() == 0);
+        assert!(game.get_fullmove_number() == 1);
+    }
+
+    #[test]
+    fn game_state() {
+        let game = Game::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        assert!(game.get_game_state() == GameState::Checkmate);
+        let game = Game::from_fen("k7/8/1QK5/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(game.get_game_state() == GameState::Stalemate);
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K1N1 w - - 0 1").unwrap();
+        assert!(game.get_game_state() == GameState::Draw(DrawReason::InsufficientMaterial));
+        let mut game = Game::from_fen("4k3/4r3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(game.get_game_state() == GameState::Check);
+        assert!(game.make_move("E1", "E2").is_err());
+        assert!(game.make_move("E1", "D1").is_ok());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Game::from_fen("").err() == Some(FenError::WrongFieldCount(0)));
+        assert!(Game::from_fen("8/8/8/8/8/8/8/8 w - - 0").err() == Some(FenError::WrongFieldCount(5)));
+        assert!(Game::from_fen("4k3/8/8/8/8/8/4K3 w - - 0 1").err() == Some(FenError::WrongRankCount(7)));
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K4 w - - 0 1").err() == Some(FenError::WrongRankLength(1)));
+        assert!(Game::from_fen("4k3/7/8/8/8/8/8/4K3 w - - 0 1").err() == Some(FenError::WrongRankLength(7)));
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K2X w - - 0 1").err() == Some(FenError::InvalidPiece('X')));
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 x - - 0 1").err() == Some(FenError::InvalidSideToMove(String::from("x"))));
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w KK - 0 1").err() == Some(FenError::InvalidCastling(String::from("KK"))));
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w A - 0 1").err() == Some(FenError::InvalidCastling(String::from("A"))));
+        // Castling rights need the king and rook on their home squares
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w KQkq - 0 1").err() == Some(FenError::InvalidCastling(String::from("KQkq"))));
+        assert!(Game::from_fen("r3k2r/8/8/8/8/8/8/R3K1R1 w K - 0 1").err() == Some(FenError::InvalidCastling(String::from("K"))));
+        assert!(Game::from_fen("r3k2r/8/8/8/8/8/8/R2K3R w Q - 0 1").err() == Some(FenError::InvalidCastling(String::from("Q"))));
+        assert!(Game::from_fen("r3k2R/8/8/8/8/8/8/R3K3 w k - 0 1").err() == Some(FenError::InvalidCastling(String::from("k"))));
+        assert!(Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").is_ok());
+        assert!(Game::from_fen("4k2r/8/8/8/8/8/8/R3K3 w Qk - 0 1").is_ok());
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/35 w - - 0 1").err() == Some(FenError::ConsecutiveDigits(1)));
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/3K13 w - - 0 1").err() == Some(FenError::ConsecutiveDigits(1)));
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - e3 0 1").err() == Some(FenError::InvalidEnPassant(String::from("e3"))));
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - i6 0 1").err() == Some(FenError::InvalidEnPassant(String::from("i6"))));
+        // The en passant square has to be behind an enemy pawn, with nothing on it or on the square the pawn came from
+        assert!(Game::from_fen("rnbqkbnr/pppp1ppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e6 0 2").err() == Some(FenError::InvalidEnPassant(String::from("e6"))));
+        assert!(Game::from_fen("4k3/8/8/4P3/8/8/8/4K3 w - e6 0 1").err() == Some(FenError::InvalidEnPassant(String::from("e6"))));
+        assert!(Game::from_fen("4k3/8/4n3/4p3/8/8/8/4K3 w - e6 0 1").err() == Some(FenError::InvalidEnPassant(String::from("e6"))));
+        assert!(Game::from_fen("4k3/4n3/8/4p3/8/8/8/4K3 w - e6 0 1").err() == Some(FenError::InvalidEnPassant(String::from("e6"))));
+        assert!(Game::from_fen("4k3/8/8/4p3/8/8/8/4K3 w - e6 0 1").is_ok());
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - -1 1").err() == Some(FenError::InvalidHalfmoveClock(String::from("-1"))));
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 0").err() == Some(FenError::InvalidFullmoveNumber(String::from("0"))));
+        assert!(Game::from_fen("8/8/8/8/8/8/8/4K3 w - - 0 1").err() == Some(FenError::WrongKingCount(Color::Black)));
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/4KK2 w - - 0 1").err() == Some(FenError::WrongKingCount(Color::White)));
+        assert!(Game::from_fen("P3k3/8/8/8/8/8/8/4K3 w - - 0 1").err() == Some(FenError::PawnOnBackRank));
+        assert!(Game::from_fen("4k3/4R3/8/8/8/8/8/4K3 w - - 0 1").err() == Some(FenError::OpponentInCheck));
+    }
+}
@@ -0,0 +1,179 @@
+use crate::zobrist::{castling_key, en_passant_key, side_key};
+use crate::{get_opposite_color, CastlingRights, Color, Game, GameState, Move, MoveOutcome, Piece};
+
+// A move that was played, with everything needed to take it back
+// The castling rights, en passant square, clocks and state are the ones from before the move
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub chess_move: Move, // The move with its flags and promotion piece filled in
+    pub piece: Piece, // The piece that moved, a pawn for promotions
+    pub captured: Option<Piece>,
+    pub san: String,
+    pub castling: CastlingRights,
+    pub en_passant: Option<(usize, usize)>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    pub state: GameState
+}
+
+impl Game {
+    // Returns every move played so far, oldest first
+    // Moves that were undone are not included
+    pub fn history(&self) -> &[HistoryEntry] {
+        &self.history
+    }
+
+    // Takes back the last move, restoring the position and game state from before it
+    // Returns the move that was taken back, or None if no moves have been played
+    // Undoing the last move of a game that was resigned or drawn by claim resumes it from before that move
+    pub fn undo(&mut self) -> Option<Move> {
+        let entry = self.history.pop()?;
+        self.revert_move(&entry);
+        self.state = entry.state;
+        self.position_history.pop();
+        self.san_moves.pop();
+        self.redo_moves.push(entry.chess_move);
+        return Some(entry.chess_move)
+    }
+
+    // Plays the last move that was taken back by undo again
+    // Returns what happened, or None if there is nothing to redo or the move can not be played now
+    // A move that can not be played, as after resigning, is kept so it can be redone later
+    // Playing any other move clears the moves that can be redone
+    pub fn redo(&mut self) -> Option<MoveOutcome> {
+        let chess_move = *self.redo_moves.last()?;
+        let mut redo_moves = std::mem::take(&mut self.redo_moves);
+        let outcome = self.play(chess_move).ok();
+        if outcome.is_some() {
+            redo_moves.pop();
+        }
+        self.redo_moves = redo_moves;
+        return outcome
+    }
+
+    // Makes a legal move with its flags filled in and passes the turn to the other player
+    // Only the position changes, the game state, history and notation are left for the caller
+    // Returns what is needed to revert the move, without its SAN
+    pub(crate) fn apply_move(&mut self, chess_move : Move) -> HistoryEntry {
+        let (from, to) = (chess_move.from, chess_move.to);
+        let entry = HistoryEntry {
+            chess_move,
+            piece: self.board[from.1][from.0].unwrap(),
+            captured: if chess_move.flags.en_passant {Some(Piece::Pawn)} else {self.board[to.1][to.0]},
+            san: String::new(),
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            state: self.state
+        };
+        self.move_piece(from.0, from.1, to.0, to.1, chess_move.promotion);
+        if self.player == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.player = get_opposite_color(self.player); // Turn is over, swap player
+        self.hash ^= side_key();
+        debug_assert!(self.hash == self.compute_hash(), "Zobrist key out of step after {}", chess_move);
+        return entry
+    }
+
+    // Reverts the position to before a move made by apply_move, which has to be the last move made
+    pub(crate) fn revert_move(&mut self, entry : &HistoryEntry) -> () {
+        let (from, to) = (entry.chess_move.from, entry.chess_move.to);
+        let color = self.get_color_at(to.0, to.1).unwrap();
+        self.relocate_piece(to.0, to.1, from.0, from.1);
+        self.place_piece(from.0, from.1, entry.piece, color);
+        // Put the rook back in its corner after castling
+        if entry.chess_move.flags.castle {
+            if to.0 == 6 {
+                self.relocate_piece(5, to.1, 7, to.1);
+            }
+            else {
+                self.relocate_piece(3, to.1, 0, to.1);
+            }
+        }
+        // A pawn taken en passant stood next to the capturing pawn, not on its destination
+        if let Some(captured) = entry.captured {
+            let (x, y) = if entry.chess_move.flags.en_passant {(to.0, from.1)} else {to};
+            self.place_piece(x, y, captured, get_opposite_color(color));
+        }
+        self.player = get_opposite_color(self.player);
+        self.hash ^= side_key() ^ castling_key(self.castling) ^ en_passant_key(self.en_passant);
+        self.castling = entry.castling;
+        self.en_passant = entry.en_passant;
+        self.hash ^= castling_key(self.castling) ^ en_passant_key(self.en_passant);
+        self.halfmove_clock = entry.halfmove_clock;
+        self.fullmove_number = entry.fullmove_number;
+        debug_assert!(self.hash == self.compute_hash(), "Zobrist key out of step after undoing {}", entry.chess_move);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_all() {
+        // Castling, en passant, a capturing promotion and a check
+        let moves = ["e4", "d5", "exd5", "c6", "dxc6", "Nf6", "cxb7", "e5", "bxa8=Q", "Bc5", "Nf3", "O-O", "d4", "exd4",
+            "c4", "dxc3", "Qxb8", "cxb2", "Bxb2", "Qxd1+", "Kxd1", "Bxf2", "Qxc8", "Rxc8"];
+        let mut game = Game::new();
+        let mut positions = Vec::new();
+        for san in moves {
+            positions.push((game.to_fen(), game.board, game.black, game.white, game.state));
+            assert!(game.make_san_move(san).is_ok(), "{}", san);
+        }
+        assert!(game.history().len() == moves.len());
+        assert!(game.history()[8].captured == Some(Piece::Rook));
+        assert!(game.history()[8].chess_move.promotion == Some(Piece::Queen));
+        assert!(game.history()[15].chess_move.flags.en_passant);
+        let last_position = game.to_fen();
+
+        while let Some((fen, board, black, white, state)) = positions.pop() {
+            assert!(game.undo().is_some());
+            assert!(game.to_fen() == fen);
+            assert!(game.board == board && game.black == black && game.white == white);
+            assert!(game.state == state);
+            assert!(game.get_san_moves().len() == positions.len());
+        }
+        assert!(game.undo().is_none());
+        assert!(game.position_history.len() == 1);
+
+        for _ in 0..moves.len() {
+            assert!(game.redo().is_some());
+        }
+        assert!(game.redo().is_none());
+        assert!(game.to_fen() == last_position);
+        assert!(game.get_san_moves() == moves);
+    }
+
+    #[test]
+    fn redo() {
+        let mut game = Game::new();
+        assert!(game.redo().is_none());
+        assert!(game.make_move("E2", "E4").is_ok());
+        assert!(game.make_move("E7", "E5").is_ok());
+        assert!(game.undo() == Some(Move::new((4, 1), (4, 3), None)));
+        assert!(game.undo() == Some(Move::new((4, 6), (4, 4), None)));
+        assert!(game.redo().is_some_and(|outcome| outcome.san == "e4"));
+        assert!(game.get_en_passant_square() == Some(String::from("E3")));
+        // Playing a different move forgets the undone one
+        assert!(game.make_move("C7", "C5").is_ok());
+        assert!(game.redo().is_none());
+        assert!(game.get_san_moves() == ["e4", "c5"]);
+
+        // Undoing resumes a resigned game
+        game.resign();
+        assert!(game.undo().is_some());
+        assert!(game.get_game_state() == GameState::InProgress);
+        assert!(game.get_player() == Color::Black);
+
+        // A move that can not be redone yet is not lost
+        game.resign();
+        assert!(game.redo().is_none());
+        assert!(game.undo().is_some());
+        assert!(game.redo().is_some_and(|outcome| outcome.san == "e4"));
+        assert!(game.redo().is_some_and(|outcome| outcome.san == "c5"));
+        assert!(game.get_san_moves() == ["e4", "c5"]);
+    }
+}
@@ -1,5 +1,33 @@
 use std::fmt;
 
+use bitboard::{bishop_attacks, bits_to_squares, color_index, piece_index, rook_attacks, square_bit, squares};
+use bitboard::{KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS};
+use zobrist::{castling_key, en_passant_key, piece_key};
+
+mod bitboard;
+mod engine;
+mod evaluation;
+mod fen;
+mod history;
+mod moves;
+mod perft;
+mod pgn;
+mod san;
+mod square;
+mod transposition;
+mod zobrist;
+
+pub use bitboard::{attacks, pawn_attacks};
+pub use engine::{Engine, SearchLimits, SearchResult, DEFAULT_TABLE_SIZE, MATE_SCORE, MAX_DEPTH};
+pub use evaluation::{evaluate, evaluate_breakdown, piece_value, Evaluation};
+pub use fen::{FenError, START_FEN};
+pub use history::HistoryEntry;
+pub use moves::{Move, MoveError, MoveFlags, MoveOutcome, UciError};
+pub use pgn::{parse_pgn, write_pgn, GameResult, PgnError, PgnErrorKind, PgnGame, PgnMove, PgnReplayError};
+pub use san::SanError;
+pub use square::{ParseSquareError, Square};
+pub use transposition::{Bound, TableEntry, TranspositionTable};
+
 // Chess pieces for use in game logic and display
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Piece {
@@ -11,146 +39,79 @@ pub enum Piece {
     Pawn
 }
 
-impl Piece {
-    // Returns a Vec of all legal moves excluding en passant and castling as coordinate tuples
-    // Does not control for check/checkmate
-    fn get_basic_moves(&self, x : usize, y : usize, game : &Game) -> Vec<(usize, usize)> {
-        let mut moves = vec![(-1, -1)]; // Initialize moves to something that will be trimmed
+// Game states
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameState {
+    InProgress, // State during normal gameplay
+    Check, // King is in check
+    GameOver, // The player to move has resigned, further play disallowed
+    Checkmate, // Checkmate, further play disallowed
+    Stalemate, // The player to move has no legal moves but is not in check, further play disallowed
+    Draw(DrawReason) // The game was drawn by rule or by a successful claim, further play disallowed
+}
 
-        // x and y could go negative during move calculation, temporarily convert to signed integers
-        let x = i32::try_from(x).unwrap();
-        let y = i32::try_from(y).unwrap();
-        
-        match self {
-            Self::King => {
-                moves = vec![
-                (x - 1, y - 1), (x, y - 1), (x + 1, y - 1),
-                (x - 1, y), (x + 1, y),
-                (x - 1, y + 1), (x, y + 1), (x + 1, y + 1)
-                ]
-            },
-            Self::Queen => {
-                moves.append(&mut self.get_moves_in_line(x, y, x, y + 7, game)); // South
-                moves.append(&mut self.get_moves_in_line(x, y, x, y - 7, game)); // North
-                moves.append(&mut self.get_moves_in_line(x, y, x + 7, y, game)); // East
-                moves.append(&mut self.get_moves_in_line(x, y, x - 7, y, game)); // West
-                moves.append(&mut self.get_moves_in_line(x, y, x + 7, y + 7, game)); // Southeast
-                moves.append(&mut self.get_moves_in_line(x, y, x + 7, y - 7, game)); // Northeast
-                moves.append(&mut self.get_moves_in_line(x, y, x - 7, y + 7, game)); // Southwest
-                moves.append(&mut self.get_moves_in_line(x, y, x - 7, y - 7, game)); // Northwest
-            },
-            Self::Rook => {
-                moves.append(&mut self.get_moves_in_line(x, y, x, y + 7, game)); // South
-                moves.append(&mut self.get_moves_in_line(x, y, x, y - 7, game)); // North
-                moves.append(&mut self.get_moves_in_line(x, y, x + 7, y, game)); // East
-                moves.append(&mut self.get_moves_in_line(x, y, x - 7, y, game)); // West
-            },
-            Self::Bishop => {
-                moves.append(&mut self.get_moves_in_line(x, y, x + 7, y + 7, game)); // Southeast
-                moves.append(&mut self.get_moves_in_line(x, y, x + 7, y - 7, game)); // Northeast
-                moves.append(&mut self.get_moves_in_line(x, y, x - 7, y + 7, game)); // Southwest
-                moves.append(&mut self.get_moves_in_line(x, y, x - 7, y - 7, game)); // Northwest
-            },
-            Self::Knight => {
-                moves = vec![
-                    (x - 1, y - 2), (x + 1, y - 2),
-                    (x - 2, y - 1), (x + 2, y - 1),
-                    (x - 2, y + 1), (x + 2, y + 1),
-                    (x - 1, y + 2), (x + 1, y + 2)
-                ]
-            },
-            Self::Pawn => {
-                match game.get_color_at(usize::try_from(x).unwrap(),usize::try_from(y).unwrap()).unwrap() {
-                    Color::Black => {
-                        if game.get_color_at(usize::try_from(x).unwrap(), usize::try_from(y + 1).unwrap()) == None {
-                            moves.push((x, y + 1));
-                        }
-                        if game.get_color_at(usize::try_from(x + 1).unwrap(), usize::try_from(y + 1).unwrap()) == Some(Color::White) {
-                            moves.push((x + 1, y + 1));
-                        }
-                        if x > 0 {
-                            if game.get_color_at(usize::try_from(x - 1).unwrap(), usize::try_from(y + 1).unwrap()) == Some(Color::White) {
-                                moves.push((x - 1, y + 1));
-                            }
-                        }
-                        if y == 1 && game.get_color_at(usize::try_from(x).unwrap(), usize::try_from(y + 2).unwrap()) == None{
-                            moves.push((x, y + 2));
-                        }
-                    },
-                    Color::White => {
-                        if game.get_color_at(usize::try_from(x).unwrap(), usize::try_from(y - 1).unwrap()) == None {
-                            moves.push((x, y - 1));
-                        }
-                        if y > 0 {
-                            if game.get_color_at(usize::try_from(x + 1).unwrap(), usize::try_from(y - 1).unwrap()) == Some(Color::Black) {
-                                moves.push((x + 1, y - 1));
-                            }
-                            if x > 0 {
-                                if game.get_color_at(usize::try_from(x - 1).unwrap(), usize::try_from(y - 1).unwrap()) == Some(Color::Black) {
-                                    moves.push((x - 1, y - 1));
-                                }
-                            }
-                        }
-                        if y == 6 && game.get_color_at(usize::try_from(x).unwrap(), usize::try_from(y - 2).unwrap()) == None{
-                            moves.push((x, y - 2));
-                        }
-                    }
-                }
-            }
+// The rule a drawn game ended by
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DrawReason {
+    FiftyMoveRule, // Claimed after 50 moves by each player without a pawn move or capture
+    SeventyFiveMoveRule, // Automatic after 75 moves by each player without a pawn move or capture
+    ThreefoldRepetition, // Claimed when the same position has occurred three times
+    FivefoldRepetition, // Automatic when the same position has occurred five times
+    InsufficientMaterial // Automatic when neither player has the material left to checkmate
+}
+
+// Castling rights for both players
+// A right is lost for good when the king or the corresponding rook moves, or when that rook is captured
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct CastlingRights {
+    pub white_king_side: bool,
+    pub white_queen_side: bool,
+    pub black_king_side: bool,
+    pub black_queen_side: bool
+}
+
+impl CastlingRights {
+    // Returns whether the given color may still castle king side
+    pub fn king_side(&self, color : Color) -> bool {
+        match color {
+            Color::Black => {self.black_king_side},
+            Color::White => {self.white_king_side}
         }
-        let moves = moves.into_iter()
-                        // Make sure all moves are in bounds
-                         .filter(|(a, b)| ((-1 < *a && *a < 8) && (-1 < *b && *b < 8)))
-                         // Convert into usize
-                         .map(|(a, b)| (usize::try_from(a).unwrap(), usize::try_from(b).unwrap())) 
-                         //Remove all moves colliding with own color
-                         .filter(|(a, b)| game.get_color_at(*a, *b) != game.get_color_at(usize::try_from(x).unwrap(),
-                                                                                                              usize::try_from(y).unwrap()))
-                         .collect();
-        return moves;
-    }
-
-    // Returns a list of all legal moves in a straight line from (x, y) to (target_x, target_y), stopping if interrupted by another piece
-    // Checks for coordinates out of bounds, meaning target_x and target_y can safely be outside of the board
-    // Diagonals _must_ have slope +/- 1
-    fn get_moves_in_line (&self, mut x : i32, mut y : i32, target_x : i32, target_y : i32, game : &Game) -> Vec<(i32, i32)> {
-        let step_x = (target_x - x).signum();
-        let step_y = (target_y - y).signum();
-        let own_color = game.get_color_at(usize::try_from(x).unwrap(), usize::try_from(y).unwrap()).unwrap();
-        let mut possible_moves = vec![(-1, -1)]; // Initialize possible moves to something that will get culled
-
-        for _i in 0..8 {
-            x += step_x;
-            y += step_y;
-
-            // End loop if new x or y out of bounds
-            if (x > 7) || (y > 7) || (x < 0) || (y < 0) {
-                break;
-            }
+    }
 
-            match game.get_color_at(usize::try_from(x).unwrap(),usize::try_from(y).unwrap()) {
-                Some(color) => {
-                    if color != own_color {
-                        possible_moves.push((x, y));
-                    }
-                    break;
-                },
-                None => {
-                    possible_moves.push((x, y));
-                },
+    // Returns whether the given color may still castle queen side
+    pub fn queen_side(&self, color : Color) -> bool {
+        match color {
+            Color::Black => {self.black_queen_side},
+            Color::White => {self.white_queen_side}
+        }
+    }
+
+    // Removes both rights of the given color, used when the king moves
+    fn remove_all(&mut self, color : Color) -> () {
+        match color {
+            Color::Black => {
+                self.black_king_side = false;
+                self.black_queen_side = false;
+            },
+            Color::White => {
+                self.white_king_side = false;
+                self.white_queen_side = false;
             }
         }
-        return possible_moves;
     }
-}
 
-// Game states
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub enum GameState {
-    InProgress, // State during normal gameplay
-    Check, // King is in check
-    GameOver, // Game has ended due to resignation/stalemate, further play disallowed *NOT USED*
-    Checkmate // Checkmate, further play disallowed
+    // Removes the right tied to the rook starting at the given numerical coordinates, if any
+    // Called for both the start and end square of every move, which covers rooks moving as well as being captured
+    fn remove_for_square(&mut self, x : usize, y : usize) -> () {
+        match (x, y) {
+            (0, 0) => {self.black_queen_side = false},
+            (7, 0) => {self.black_king_side = false},
+            (0, 7) => {self.white_queen_side = false},
+            (7, 7) => {self.white_king_side = false},
+            _ => {}
+        }
+    }
 }
 
 // Colors for use in movement, turn-taking and display logic
@@ -160,21 +121,38 @@ pub enum Color {
     White
 }
 
+#[derive(Clone)]
 pub struct Game {
     // The board stored as a 2D matrix. Access a square with self.board[y][x], with the origin at the top left corner
     // Empty squares are represented by None, occupied squares by Some(Piece)
     board: [[Option<Piece>; 8]; 8],
     black: u64, // Positions of black pieces as a bitboard
     white: u64, // And the same for white pieces
+    pieces: [u64; 6], // Positions of each piece type of either color, indexed by piece_index
     state: GameState,
     player: Color, // The player to move
-    promotion_piece : Piece // The piece type that pawns will promote to
+    castling: CastlingRights, // Which castling moves are still allowed
+    en_passant: Option<(usize, usize)>, // The square skipped by a pawn's double push on the previous move, if any
+    halfmove_clock: u32, // Moves made by either player since the last pawn move or capture
+    fullmove_number: u32, // Starts at 1 and is incremented after every move by black
+    hash: u64, // Zobrist key of the current position, kept up to date as pieces move
+    position_history: Vec<u64>, // Repetition keys of every position that has occurred in the game, including the current one
+    start_fen: String, // The position the game started from, in Forsyth-Edwards Notation
+    san_moves: Vec<String>, // Every move played so far, in Standard Algebraic Notation
+    history: Vec<HistoryEntry>, // Every move played so far, with what is needed to undo it
+    redo_moves: Vec<Move> // Moves taken back by undo, the most recently undone last
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
 }
 
 impl Game {
     // Constructs a Game instance
     pub fn new() -> Game {
-        Game {
+        let mut game = Game {
             // Places the pieces in the starting position
             board : [
                 [Some(Piece::Rook), Some(Piece::Knight), Some(Piece::Bishop), Some(Piece::Queen), Some(Piece::King), Some(Piece::Bishop), Some(Piece::Knight), Some(Piece::Rook)],
@@ -188,146 +166,646 @@ impl Game {
             ],
             black: 0xFF_FF_00_00_00_00_00_00, // Sets top two rows to black
             white: 0x00_00_00_00_00_00_FF_FF, // And bottom two rows to white
+            pieces: [0; 6], // Also filled in from the board below
             state: GameState::InProgress,
             player: Color::White, // White to move
-            promotion_piece: Piece::Queen
-        }
+            castling: CastlingRights {
+                white_king_side: true,
+                white_queen_side: true,
+                black_king_side: true,
+                black_queen_side: true
+            },
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            hash: 0, // Computed from the board below
+            position_history: Vec::new(),
+            start_fen: START_FEN.to_string(),
+            san_moves: Vec::new(),
+            history: Vec::new(),
+            redo_moves: Vec::new()
+        };
+        game.sync_pieces();
+        let start_position = game.position_key();
+        game.position_history.push(start_position);
+        return game
     }
 
     // Takes standard chess coordinates as inputs
+    // A pawn reaching the last rank has to name its promotion piece after the end position, as in "E8=Q"
     // If the move is legal and the state allows:
-    // Makes the move, sets and returns the resulting game state, and advances the turn to the other player (if not checkmated)
-    // Any illegal move returns None
-    pub fn make_move(&mut self, _from: &str, _to: &str) -> Option<GameState> {
-        let numerical_from = string_to_coordinates(_from);
-        let numerical_to = string_to_coordinates(_to);
-        // First check that the move is allowed, if not exit early and return None
+    // Makes the move, sets the resulting game state, advances the turn to the other player and returns what happened
+    // Otherwise returns why the move could not be made
+    pub fn make_move(&mut self, _from: &str, _to: &str) -> Result<MoveOutcome, MoveError> {
+        let numerical_from = string_to_coordinates(_from)?;
+        let (numerical_to, promotion) = match _to.split_once('=') {
+            Some((square, piece)) => {
+                let mut letters = piece.chars();
+                // The piece letter can be in either case, like the coordinates
+                let letter = letters.next().map(|letter| letter.to_ascii_uppercase());
+                let promotion = match (letter.and_then(san::letter_to_piece), letters.next()) {
+                    (Some(promotion), None) => {promotion},
+                    _ => {return Err(MoveError::UnknownPromotion(piece.to_string()))}
+                };
+                (string_to_coordinates(square)?, Some(promotion))
+            },
+            None => {(string_to_coordinates(_to)?, None)}
+        };
+        return self.play(Move::new(numerical_from, numerical_to, promotion))
+    }
+
+    // Plays a move if it is legal and the state allows, like make_move
+    // A pawn reaching the last rank promotes to the move's promotion piece, which no other move may have
+    // The move's flags are ignored, they are worked out from the position
+    pub fn play(&mut self, chess_move : Move) -> Result<MoveOutcome, MoveError> {
+        let played = self.legal_move(chess_move)?;
+        let mut san = san::move_to_san(self, played);
+        let mut entry = self.apply_move(played);
+        let position = self.position_key();
+        self.position_history.push(position);
+        self.update_state();
+        if self.state == GameState::Checkmate {
+            san.push('#');
+        }
+        else if self.is_in_check(self.player) {
+            san.push('+');
+        }
+        self.san_moves.push(san.clone());
+        entry.san = san.clone();
+        let captured = entry.captured;
+        self.history.push(entry);
+        self.redo_moves.clear();
+        return Ok(MoveOutcome { chess_move: played, captured, san, state: self.state });
+    }
+
+    // Returns the move with its flags filled in if it is legal and the state allows, otherwise why it is not
+    fn legal_move(&self, chess_move : Move) -> Result<Move, MoveError> {
+        let (numerical_from, numerical_to) = (chess_move.from, chess_move.to);
+        // First check that the move is allowed, if not exit early with the reason
+        self.check_move(numerical_from, numerical_to)?;
+        // Exit if the promotion does not fit the move
+        let reaches_last_rank = self.board[numerical_from.1][numerical_from.0] == Some(Piece::Pawn)
+            && (numerical_to.1 == 0 || numerical_to.1 == 7);
+        match chess_move.promotion {
+            None if reaches_last_rank => {return Err(MoveError::MissingPromotion)},
+            Some(piece) if !reaches_last_rank || !PROMOTION_PIECES.contains(&piece) => {
+                return Err(MoveError::InvalidPromotion(piece))
+            },
+            _ => {}
+        }
+        return Ok(self.describe_move(numerical_from, numerical_to, chess_move.promotion))
+    }
+
+    // Checks that the piece on the start square may move to the end square, ignoring promotion
+    fn check_move(&self, from : (usize, usize), to : (usize, usize)) -> Result<(), MoveError> {
         // Exit if game state prohibits moving
-        if !(self.state == GameState::InProgress || self.state == GameState::Check) {
-            return None;
+        if self.is_finished() {
+            return Err(MoveError::GameOver(self.state));
         }
-        // Exit if attempting to move out of turn
-        if self.get_color_at(numerical_from.0, numerical_from.1) != Some(self.player) {
-            return None;
+        // Exit if coordinates are outside the board
+        Square::new(from.0, from.1)?;
+        Square::new(to.0, to.1)?;
+        // Exit if there is nothing to move, or if attempting to move out of turn
+        let color = match self.get_color_at(from.0, from.1) {
+            None => {return Err(MoveError::EmptySquare)},
+            Some(color) if color != self.player => {return Err(MoveError::WrongPlayer(color))},
+            Some(color) => {color}
+        };
+        // Exit if the piece can not reach the end position at all
+        let piece = self.board[from.1][from.0].unwrap();
+        let is_castling = piece == Piece::King && self.get_castling_moves(from.0, from.1, color).contains(&to);
+        if self.basic_moves(from.0, from.1) & square_bit(to.0, to.1) == 0 && !is_castling {
+            return Err(MoveError::IllegalDestination);
         }
-        let moves = self.get_possible_moves(_from);
-        // Exit if start position is invalid
-        if moves == None {
-            return None;
+        // Exit if the move would leave the king attacked
+        if self.in_check_after_move(from.0, from.1, to.0, to.1, None, color) {
+            if self.is_in_check(color) {
+                return Err(MoveError::StillInCheck);
+            }
+            if piece == Piece::King {
+                return Err(MoveError::KingIntoCheck);
+            }
+            return Err(MoveError::PinnedPiece);
         }
-        let moves = moves.unwrap();
-        // Exit if end position is invalid
-        if !moves.contains(&String::from(_to)) {
-            return None;
+        return Ok(())
+    }
+
+    // Returns every legal move for the player to move
+    // A pawn reaching the last rank gives one move for each piece it can promote to
+    pub fn legal_moves(&self) -> Vec<Move> {
+        if self.is_finished() {
+            return Vec::new();
+        }
+        return self.generate_moves()
+    }
+
+    // Returns every legal capture and promotion for the player to move, including en passant
+    // A pawn reaching the last rank gives one move for each piece it can promote to, whether it captures or not
+    pub fn legal_captures(&self) -> Vec<Move> {
+        if self.is_finished() {
+            return Vec::new();
+        }
+        return self.generate_captures()
+    }
+
+    // Returns every legal move in the position, regardless of whether the game is finished
+    fn generate_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for (x, y) in squares(self.color_bits(self.player)) {
+            for to in self.get_legal_destinations(x, y) {
+                self.add_move(&mut moves, (x, y), to);
+            }
+        }
+        return moves
+    }
+
+    // Returns every legal capture and promotion in the position, regardless of whether the game is finished
+    // Castling never captures, so unlike generate_moves this can go straight from the basic moves
+    fn generate_captures(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let color = self.player;
+        let opponent = self.color_bits(get_opposite_color(color));
+        let en_passant = self.en_passant.map_or(0, |(x, y)| square_bit(x, y));
+        let last_rank = if color == Color::White {0xFF_00_00_00_00_00_00_00} else {0xFF};
+        for (x, y) in squares(self.color_bits(color)) {
+            let targets = if self.board[y][x] == Some(Piece::Pawn) {opponent | en_passant | last_rank} else {opponent};
+            for (to_x, to_y) in squares(self.basic_moves(x, y) & targets) {
+                if !self.in_check_after_move(x, y, to_x, to_y, None, color) {
+                    self.add_move(&mut moves, (x, y), (to_x, to_y));
+                }
+            }
+        }
+        return moves
+    }
+
+    // Adds the legal move between the given squares to the list, once for each promotion piece if a pawn promotes
+    fn add_move(&self, moves : &mut Vec<Move>, from : (usize, usize), to : (usize, usize)) -> () {
+        if self.board[from.1][from.0] == Some(Piece::Pawn) && (to.1 == 0 || to.1 == 7) {
+            for piece in PROMOTION_PIECES {
+                moves.push(self.describe_move(from, to, Some(piece)));
+            }
+        }
+        else {
+            moves.push(self.describe_move(from, to, None));
         }
-        // We now know that the move is legal, proceed from there
-        self.move_piece(numerical_from.0, numerical_from.1, numerical_to.0, numerical_to.1);
-        let opponent = get_opposite_color(self.player);
-        if self.is_in_check(opponent) {
-            if self.has_no_moves(opponent) {
+    }
+
+    // Builds the Move for a legal move between the given numerical coordinates, working out its flags
+    fn describe_move(&self, from : (usize, usize), to : (usize, usize), promotion : Option<Piece>) -> Move {
+        let piece = self.board[from.1][from.0].unwrap();
+        let is_pawn = piece == Piece::Pawn;
+        let en_passant = is_pawn && from.0 != to.0 && self.board[to.1][to.0].is_none();
+        let flags = MoveFlags {
+            capture: self.board[to.1][to.0].is_some() || en_passant,
+            castle: piece == Piece::King && from.0.abs_diff(to.0) == 2,
+            en_passant,
+            double_push: is_pawn && from.1.abs_diff(to.1) == 2
+        };
+        return Move { from, to, promotion, flags }
+    }
+
+    // Sets the game state from the point of view of the player to move
+    fn update_state(&mut self) -> () {
+        let no_moves = self.has_no_moves(self.player);
+        if self.is_in_check(self.player) {
+            if no_moves {
                 self.state = GameState::Checkmate;
             }
             else {
                 self.state = GameState::Check
             }
         }
+        else if no_moves {
+            self.state = GameState::Stalemate;
+        }
         else {
             self.state = GameState::InProgress;
         }
-        self.player = opponent; // Turn is over, swap player
-        return Some(self.state);
+        // Checkmate and stalemate end the game before any automatic draw can apply
+        if !self.is_finished() {
+            if self.is_insufficient_material(Color::White) && self.is_insufficient_material(Color::Black) {
+                self.state = GameState::Draw(DrawReason::InsufficientMaterial);
+            }
+            else if self.halfmove_clock >= 150 {
+                self.state = GameState::Draw(DrawReason::SeventyFiveMoveRule);
+            }
+            else if self.repetition_count() >= 5 {
+                self.state = GameState::Draw(DrawReason::FivefoldRepetition);
+            }
+        }
+    }
+
+    // Returns the rule the player to move could claim a draw by, if any
+    pub fn can_claim_draw(&self) -> Option<DrawReason> {
+        if self.is_finished() {
+            return None;
+        }
+        if self.halfmove_clock >= 100 {
+            return Some(DrawReason::FiftyMoveRule);
+        }
+        if self.repetition_count() >= 3 {
+            return Some(DrawReason::ThreefoldRepetition);
+        }
+        return None
+    }
+
+    // Claims a draw by the fifty-move rule or threefold repetition
+    // If the claim is valid, ends the game and returns the resulting game state
+    // Else returns None and the game continues
+    pub fn claim_draw(&mut self) -> Option<GameState> {
+        let reason = self.can_claim_draw()?;
+        self.state = GameState::Draw(reason);
+        return Some(self.state)
+    }
+
+    // Returns whether the given color can never checkmate, however the game continues
+    // This is the case with a lone king, a king and knight against a lone king, or only bishops that all
+    // stand on squares of one shade while the opponent has nothing that could block on the other shade
+    pub fn is_insufficient_material(&self, color : Color) -> bool {
+        let mut knights = 0;
+        let mut bishop_shades = Vec::new(); // Shades of the squares all bishops on the board stand on
+        let mut own_bishops = 0;
+        let mut opponent_has_other = false; // Whether the opponent has pieces other than king and bishops
+        for y in 0..8 {
+            for x in 0..8 {
+                let piece = match self.board[y][x] {
+                    Some(Piece::King) | None => {continue},
+                    Some(piece) => {piece}
+                };
+                let own = self.get_color_at(x, y) == Some(color);
+                match piece {
+                    Piece::Bishop => {
+                        bishop_shades.push((x + y) % 2);
+                        if own {
+                            own_bishops += 1;
+                        }
+                    },
+                    Piece::Knight if own => {knights += 1},
+                    _ if own => {return false},
+                    _ => {opponent_has_other = true}
+                }
+            }
+        }
+        if knights == 0 && own_bishops == 0 {
+            return true;
+        }
+        if opponent_has_other {
+            return false;
+        }
+        if knights == 1 && own_bishops == 0 {
+            return bishop_shades.is_empty();
+        }
+        if knights == 0 {
+            return bishop_shades.iter().all(|shade| *shade == bishop_shades[0]);
+        }
+        return false
+    }
+
+    // Returns whether the game has ended and further play is disallowed
+    fn is_finished(&self) -> bool {
+        !(self.state == GameState::InProgress || self.state == GameState::Check)
+    }
+
+    // Returns how many times the current position has occurred, including now
+    fn repetition_count(&self) -> usize {
+        let current = self.position_history.last().unwrap();
+        self.position_history.iter().filter(|position| *position == current).count()
+    }
+
+    // Returns the repetition key of the current position, which is its Zobrist key
+    // Two positions are the same if the same pieces are on the same squares, with the same player to move and the same
+    // moves available, so the en passant file is taken back out of the key unless a pawn can actually capture there
+    pub(crate) fn position_key(&self) -> u64 {
+        let can_capture = match self.en_passant {
+            Some((x, y)) => {
+                // Pawns able to capture stand next to the pawn that just moved, one row behind the target square
+                let pawn_y = match self.player {
+                    Color::Black => {y - 1},
+                    Color::White => {y + 1}
+                };
+                let player = self.player;
+                [x.wrapping_sub(1), x + 1].into_iter()
+                    .filter(|pawn_x| *pawn_x < 8)
+                    .any(|pawn_x| self.board[pawn_y][pawn_x] == Some(Piece::Pawn)
+                        && self.get_color_at(pawn_x, pawn_y) == Some(player)
+                        && !self.in_check_after_move(pawn_x, pawn_y, x, y, None, player))
+            },
+            None => {false}
+        };
+        if can_capture {self.hash} else {self.hash ^ en_passant_key(self.en_passant)}
+    }
+
+    // The player to move resigns, ending the game with GameState::GameOver
+    // The player returned by get_player is then the one who resigned
+    // Returns None if the game has already ended
+    pub fn resign(&mut self) -> Option<GameState> {
+        if self.is_finished() {
+            return None;
+        }
+        self.state = GameState::GameOver;
+        return Some(self.state)
     }
 
     // Moves piece without checks, will panic at an invalid move
     // Replaces whatever is at the end position, meaning captures happen automatically
-    // Promotes pawns if they reach the end of the board
-    fn move_piece(&mut self, start_x : usize, start_y : usize, end_x : usize, end_y : usize) -> () {
-        let piece = self.board[start_y][start_x].clone().unwrap();
+    // A king moving two squares is treated as castling and brings the rook along
+    // A pawn moving diagonally to an empty square is treated as en passant and removes the passed pawn
+    // Updates castling rights, the en passant square, the halfmove clock and the Zobrist key, but not the player to move
+    // Pawns that reach the end of the board promote to the given piece, which the other moves ignore
+    fn move_piece(&mut self, start_x : usize, start_y : usize, end_x : usize, end_y : usize, promotion : Option<Piece>) -> () {
+        let piece = self.board[start_y][start_x].unwrap();
         let color = self.get_color_at(start_x, start_y).unwrap();
-        self.board[start_y][start_x] = None;
-        self.set_color_at(start_x, start_y, None);
-        self.board[end_y][end_x] = Some(piece);
-        self.set_color_at(end_x, end_y, Some(color));
-        // Start check for promotion
-        if piece != Piece::Pawn {
-            return
+        let is_en_passant = piece == Piece::Pawn && start_x != end_x && self.board[end_y][end_x].is_none();
+        if piece == Piece::Pawn || self.board[end_y][end_x].is_some() {
+            self.halfmove_clock = 0;
+        }
+        else {
+            self.halfmove_clock += 1;
+        }
+        // Take the old rights and en passant file out of the key, the new ones go back in at the end
+        self.hash ^= castling_key(self.castling) ^ en_passant_key(self.en_passant);
+        self.en_passant = None;
+        self.relocate_piece(start_x, start_y, end_x, end_y);
+        self.castling.remove_for_square(start_x, start_y);
+        self.castling.remove_for_square(end_x, end_y);
+        if piece == Piece::King {
+            self.castling.remove_all(color);
+            // Move the rook to the other side of the king when castling
+            if start_x == 4 && end_x == 6 {
+                self.relocate_piece(7, end_y, 5, end_y);
+            }
+            else if start_x == 4 && end_x == 2 {
+                self.relocate_piece(0, end_y, 3, end_y);
+            }
+        }
+        if piece == Piece::Pawn {
+            if is_en_passant {
+                self.remove_piece(end_x, start_y);
+            }
+            if start_y.abs_diff(end_y) == 2 {
+                self.en_passant = Some((start_x, (start_y + end_y) / 2));
+            }
+            // Start check for promotion
+            let last_rank = match color {
+                Color::Black => {7},
+                Color::White => {0}
+            };
+            if end_y == last_rank {
+                self.place_piece(end_x, end_y, promotion.expect("a pawn reaching the last rank needs a promotion piece"), color);
+            }
         }
+        self.hash ^= castling_key(self.castling) ^ en_passant_key(self.en_passant);
+    }
+
+    // Moves the piece and its color from the start to the end position without any other side effects
+    fn relocate_piece(&mut self, start_x : usize, start_y : usize, end_x : usize, end_y : usize) -> () {
+        let piece = self.board[start_y][start_x].unwrap();
+        let color = self.get_color_at(start_x, start_y).unwrap();
+        self.remove_piece(start_x, start_y);
+        self.place_piece(end_x, end_y, piece, color);
+    }
+
+    // Puts a piece of the given color on the square at the given numerical coordinates, replacing whatever stood there
+    // Keeps the board, the color bitboards and the piece bitboards in step
+    fn place_piece(&mut self, x : usize, y : usize, piece : Piece, color : Color) -> () {
+        self.remove_piece(x, y);
+        self.hash ^= piece_key(piece, color, x, y);
+        self.board[y][x] = Some(piece);
+        self.pieces[piece_index(piece)] |= square_bit(x, y);
+        self.set_color_at(x, y, Some(color));
+    }
+
+    // Empties the square at the given numerical coordinates
+    fn remove_piece(&mut self, x : usize, y : usize) -> () {
+        if let (Some(piece), Some(color)) = (self.board[y][x], self.get_color_at(x, y)) {
+            self.pieces[piece_index(piece)] &= !square_bit(x, y);
+            self.hash ^= piece_key(piece, color, x, y);
+        }
+        self.board[y][x] = None;
+        self.set_color_at(x, y, None);
+    }
+
+    // Rebuilds the piece bitboards and the Zobrist key from the board, the color bitboards and the other fields, for
+    // when those were set directly
+    fn sync_pieces(&mut self) -> () {
+        self.pieces = [0; 6];
+        for (x, y) in squares(self.black | self.white) {
+            if let Some(piece) = self.board[y][x] {
+                self.pieces[piece_index(piece)] |= square_bit(x, y);
+            }
+        }
+        self.hash = self.compute_hash();
+    }
+
+    // Returns a copy of the position with none of the game's history, to try moves on without copying all of it
+    // Repetitions and the moves played so far are unknown to the copy
+    fn position_copy(&self) -> Game {
+        Game {
+            position_history: Vec::new(),
+            start_fen: String::new(),
+            san_moves: Vec::new(),
+            history: Vec::new(),
+            redo_moves: Vec::new(),
+            ..*self
+        }
+    }
+
+    // Returns the bitboard of every piece of the given color
+    fn color_bits(&self, color : Color) -> u64 {
         match color {
-            Color::Black => {
-                if end_y == 7 {
-                    self.board[end_y][end_x] = Some(self.promotion_piece);
-                }
+            Color::Black => {self.black},
+            Color::White => {self.white}
+        }
+    }
+
+    // Returns the squares the piece at the given numerical coordinates can move to as a bitboard
+    // Does not control for check, and castling is generated separately by get_castling_moves
+    // Apart from pawn pushes these are also the squares the piece attacks
+    fn basic_moves(&self, x : usize, y : usize) -> u64 {
+        let (piece, color) = match (self.board[y][x], self.get_color_at(x, y)) {
+            (Some(piece), Some(color)) => {(piece, color)},
+            _ => {return 0}
+        };
+        let index = 8 * y + x;
+        let occupancy = self.black | self.white;
+        let moves = match piece {
+            Piece::King => {KING_ATTACKS[index]},
+            Piece::Knight => {KNIGHT_ATTACKS[index]},
+            Piece::Rook => {rook_attacks(index, occupancy)},
+            Piece::Bishop => {bishop_attacks(index, occupancy)},
+            Piece::Queen => {
+                rook_attacks(index, occupancy) | bishop_attacks(index, occupancy)
             },
-            Color::White => {
-                if end_y == 0 {
-                    self.board[end_y][end_x] = Some(self.promotion_piece);
+            Piece::Pawn => {
+                // Only the player to move can capture en passant
+                let en_passant = match self.en_passant {
+                    Some((target_x, target_y)) if color == self.player => {square_bit(target_x, target_y)},
+                    _ => {0}
+                };
+                let captures = PAWN_ATTACKS[color_index(color)][index] & (self.color_bits(get_opposite_color(color)) | en_passant);
+                // A double step needs both squares in front of the pawn to be empty
+                let (last_y, start_y) = match color {
+                    Color::Black => {(7, 1)},
+                    Color::White => {(0, 6)}
+                };
+                let mut pushes = 0;
+                if y != last_y {
+                    let forward_y = if color == Color::Black {y + 1} else {y - 1};
+                    pushes = square_bit(x, forward_y) & !occupancy;
+                    if pushes != 0 && y == start_y {
+                        let double_y = if color == Color::Black {y + 2} else {y - 2};
+                        pushes |= square_bit(x, double_y) & !occupancy;
+                    }
                 }
+                captures | pushes
             }
-        }
+        };
+        return moves & !self.color_bits(color)
     }
 
     // If any moves for the given color are possible, return false
     // Else return true
-    fn has_no_moves(&mut self, color : Color) -> bool {
-        for y in 0..8 {
-            for x in 0..8 {
-                if self.get_color_at(x, y) == Some(color) {
-                    let moves = self.get_possible_moves(&coordinates_to_string(x, y)).unwrap();
-                    if moves.len() > 0 {return false };
-                }
+    fn has_no_moves(&self, color : Color) -> bool {
+        for (x, y) in squares(self.color_bits(color)) {
+            if !self.get_legal_destinations(x, y).is_empty() {
+                return false
             }
         }
         return true
     }
 
-    // Simulates a move and restores the board, returns true if the move would put color in check, otherwise false 
-    fn in_check_after_move(&mut self, start_x: usize, start_y : usize, end_x : usize, end_y : usize, color : Color) -> bool {
-        let original_board = self.board.clone();
-        let original_black = self.black;
-        let original_white = self.white;
-        self.move_piece(start_x, start_y, end_x, end_y);
-        let in_check = self.is_in_check(color);
-        self.board = original_board;
-        self.black = original_black;
-        self.white = original_white;
-        return in_check
-    }
-
-    // Returns whether the king of specified color at specified coordinates is in check
-    // Takes coordinates and color separately to be able to predict check for future position
-    fn is_in_check(&self, color : Color) -> bool {
-        let opposite_color = get_opposite_color(color);
-        let mut all_opponent_moves : Vec<(usize, usize)> = vec![(0, 0)]; // Initialize to value that will be trimmed
-        for y in 0..8 {
-            for x in 0..8 {
-                match self.board[y][x] {
-                    Some(piece) => {
-                        if self.get_color_at(x, y).unwrap() == opposite_color {
-                            all_opponent_moves.append(&mut piece.get_basic_moves(x, y, self));
-                        }
-                    },
-                    None => {},
+    // Returns true if the king of color would be in check after the move, otherwise false
+    // The board is not changed: the king's square is looked at through the occupancy the move leaves behind, without
+    // the piece it captures, so the attack tables find pins, attacks on the king's destination and discovered checks
+    // Castling moves the rook as well, but the king's path is already checked by get_castling_moves
+    // promotion is the piece a pawn becomes on the last rank, which only matters for a check on the other color's king
+    fn in_check_after_move(&self, start_x: usize, start_y : usize, end_x : usize, end_y : usize, promotion : Option<Piece>, color : Color) -> bool {
+        let piece = self.board[start_y][start_x].unwrap();
+        let mover = self.get_color_at(start_x, start_y).unwrap();
+        let (from, to) = (square_bit(start_x, start_y), square_bit(end_x, end_y));
+        // A pawn taken en passant stands next to the capturing pawn, not on its destination
+        let mut captured = to;
+        if piece == Piece::Pawn && start_x != end_x && self.board[end_y][end_x].is_none() {
+            captured |= square_bit(end_x, start_y);
+        }
+        let occupancy = (self.black | self.white) & !from & !captured | to;
+        if mover == color {
+            let king = match piece {
+                Piece::King => {8 * end_y + end_x},
+                _ => {
+                    match self.find_king(color) {
+                        Some(king) => {king.index()},
+                        None => {return false}
+                    }
                 }
-            }
+            };
+            return self.attackers_through(king, get_opposite_color(color), occupancy) & !captured != 0
         }
-        all_opponent_moves.swap_remove(0); // Trim initialization value
-        return all_opponent_moves.contains(&self.find_king(color))
+        // The opponent's move gives check with the piece that moved or by uncovering another
+        let king = match self.find_king(color) {
+            Some(king) => {king},
+            None => {return false}
+        };
+        let destination = Square::at(end_x, end_y);
+        let moved_attacks = match promotion.unwrap_or(piece) {
+            Piece::Pawn => {pawn_attacks(mover, destination)},
+            moved => {attacks(moved, destination, occupancy)}
+        };
+        return moved_attacks & king.bit() != 0 || self.attackers_through(king.index(), mover, occupancy) & !from != 0
     }
 
-    // Returns the numerical coordinates of the king of the given color
-    fn find_king(&self, color: Color) -> (usize, usize) {
-        for y in 0..8 {
-            for x in 0..8 {
-                if self.board[y][x] == Some(Piece::King) && self.get_color_at(x, y) == Some(color) {
-                    return (x, y)
-                }
+    // Returns whether the king of the specified color is in check
+    // A color without a king on the board is never in check
+    pub fn is_in_check(&self, color : Color) -> bool {
+        match self.find_king(color) {
+            Some(king) => {self.is_square_attacked(king, get_opposite_color(color))},
+            None => {false}
+        }
+    }
+
+    // Returns the squares of the pieces giving check to the king of the player to move, empty if not in check
+    pub fn checkers(&self) -> Vec<Square> {
+        match self.find_king(self.player) {
+            Some(king) => {bits_to_squares(self.attacker_bits(king.index(), get_opposite_color(self.player)))},
+            None => {Vec::new()}
+        }
+    }
+
+    // Returns whether any piece of the given color attacks the square
+    // Pieces attack the squares they could capture on, so pawns attack diagonally and pinned pieces still attack
+    pub fn is_square_attacked(&self, square : Square, by_color : Color) -> bool {
+        self.attacker_bits(square.index(), by_color) != 0
+    }
+
+    // Returns the squares of every piece of either color attacking the square, starting from A8
+    pub fn attackers_of(&self, square : Square) -> Vec<Square> {
+        let index = square.index();
+        bits_to_squares(self.attacker_bits(index, Color::White) | self.attacker_bits(index, Color::Black))
+    }
+
+    // Returns the pieces of the given color attacking the square at index as a bitboard
+    fn attacker_bits(&self, index : usize, by_color : Color) -> u64 {
+        self.attackers_through(index, by_color, self.black | self.white)
+    }
+
+    // Returns the pieces of the given color attacking the square at index as a bitboard, with sliding pieces blocked
+    // by the given occupancy instead of the board's
+    // Looks outwards from the square: a piece attacks it if the same kind of piece on the square would attack that piece
+    fn attackers_through(&self, index : usize, by_color : Color, occupancy : u64) -> u64 {
+        let attackers = self.color_bits(by_color);
+        let pieces = |piece : Piece| self.pieces[piece_index(piece)] & attackers;
+        let queens = pieces(Piece::Queen);
+        return PAWN_ATTACKS[color_index(get_opposite_color(by_color))][index] & pieces(Piece::Pawn)
+            | KNIGHT_ATTACKS[index] & pieces(Piece::Knight)
+            | KING_ATTACKS[index] & pieces(Piece::King)
+            | bishop_attacks(index, occupancy) & (pieces(Piece::Bishop) | queens)
+            | rook_attacks(index, occupancy) & (pieces(Piece::Rook) | queens)
+    }
+
+    // Returns the castling moves available to the king of the given color standing at (x, y)
+    // Requires the castling right, the rook in its corner, empty squares in between, and that the king
+    // is not in check and does not pass through or land on an attacked square
+    fn get_castling_moves(&self, x : usize, y : usize, color : Color) -> Vec<(usize, usize)> {
+        let mut moves = Vec::new();
+        let home_y = match color {
+            Color::Black => {0},
+            Color::White => {7}
+        };
+        if x != 4 || y != home_y {
+            return moves;
+        }
+        let opponent = get_opposite_color(color);
+        let has_rook = |rook_x : usize| {
+            self.board[y][rook_x] == Some(Piece::Rook) && self.get_color_at(rook_x, y) == Some(color)
+        };
+        let is_empty = |path_x : usize| self.get_color_at(path_x, y).is_none();
+        let is_safe = |path_x : usize| !self.is_square_attacked(Square::at(path_x, y), opponent);
+
+        if (self.castling.king_side(color) || self.castling.queen_side(color)) && is_safe(x) {
+            if self.castling.king_side(color) && has_rook(7)
+                && [5, 6].into_iter().all(|path_x| is_empty(path_x) && is_safe(path_x)) {
+                moves.push((6, y));
+            }
+            // The rook passes B1/B8, so it must be empty but may be attacked
+            if self.castling.queen_side(color) && has_rook(0) && is_empty(1)
+                && [3, 2].into_iter().all(|path_x| is_empty(path_x) && is_safe(path_x)) {
+                moves.push((2, y));
             }
         }
-        panic!("King not found!");
+        return moves
+    }
+
+    // Returns the square of the king of the given color, or None if it has no king on the board
+    pub fn find_king(&self, color: Color) -> Option<Square> {
+        squares(self.pieces[piece_index(Piece::King)] & self.color_bits(color)).next().map(|(x, y)| Square::at(x, y))
     }
+
     // Returns the color, if there is one, at the specified x, y coordinates
     // Empty squares or invalid coordinates return None
     pub fn get_color_at(&self, x: usize, y: usize) -> Option<Color> {
+        if x > 7 || y > 7 {
+            return None;
+        }
         let index = 8 * y + x;
         let position : u64 = 0x80_00_00_00_00_00_00_00u64 >> index;
 
@@ -352,52 +830,59 @@ impl Game {
             Some(color) => {
                 match color {
                     Color::Black => {
-                        self.black = self.black | position; // set the bit at position to 1
-                        self.white = self.white & !position; // set the bit at position to 0
+                        self.black |= position; // set the bit at position to 1
+                        self.white &= !position; // set the bit at position to 0
                     },
                     Color::White => {
-                        self.black = self.black & !position;
-                        self.white = self.white | position;
+                        self.black &= !position;
+                        self.white |= position;
                     }
                 }
             },
             None => {
-                self.black = self.black & !position;
-                self.white = self.white & !position;
+                self.black &= !position;
+                self.white &= !position;
             },
         }
     }
 
-    // Sets the piece type for promotion.
-    pub fn set_promotion(&mut self, _piece: Piece) -> () {
-        if _piece != Piece::King {
-            self.promotion_piece = _piece;
-        }
-        else {
-            panic!("Can not promote to king!")
+    // Takes a position in standard chess coordinates
+    // Returns all possible moves of the piece there as standard chess coordinates, none for an empty square
+    // A pawn reaching the last rank lists its destination once for each promotion, as in "E8=Q"
+    // Coordinates that are not a square return an error
+    pub fn get_possible_moves(&self, _position: &str) -> Result<Vec<String>, ParseSquareError> {
+        let _position = string_to_coordinates(_position)?;
+        let is_pawn = self.board[_position.1][_position.0] == Some(Piece::Pawn);
+        let mut moves = Vec::new();
+        for (to_x, to_y) in self.get_legal_destinations(_position.0, _position.1) {
+            let destination = Square::at(to_x, to_y).to_string();
+            if is_pawn && (to_y == 0 || to_y == 7) {
+                for piece in PROMOTION_PIECES {
+                    moves.push(format!("{}={}", destination, san::piece_to_letter(piece)));
+                }
+            }
+            else {
+                moves.push(destination);
+            }
         }
+        return Ok(moves)
     }
 
-    // Takes a position in standard chess coordinates
-    // If starting position is valid, returns all possible moves as standard chess coordinates
-    // Else returns None
-    pub fn get_possible_moves(&mut self, _position: &str) -> Option<Vec<String>> {
-        let _position = string_to_coordinates(_position);
-        let x = _position.0;
-        let y = _position.1;
-        match self.board[y][x] {
-            Some(piece) => {
-                let color = self.get_color_at(x, y).unwrap();
-                let moves = piece.get_basic_moves(x, y, self);
-                let moves = moves
-                                                .into_iter()
-                                                .filter(|(to_x, to_y)| !self.in_check_after_move(x, y, *to_x, *to_y, color))
-                                                .map(|(to_x, to_y)| coordinates_to_string(to_x, to_y))
-                                                .collect::<Vec<String>>();
-                return Some(moves);
-            },
-            None => {return None}
+    // Returns the numerical coordinates of every square the piece at (x, y) can legally move to
+    // Empty squares have no moves
+    fn get_legal_destinations(&self, x : usize, y : usize) -> Vec<(usize, usize)> {
+        let piece = match self.board[y][x] {
+            Some(piece) => {piece},
+            None => {return Vec::new()}
+        };
+        let color = self.get_color_at(x, y).unwrap();
+        let mut moves = squares(self.basic_moves(x, y)).collect::<Vec<(usize, usize)>>();
+        if piece == Piece::King {
+            moves.append(&mut self.get_castling_moves(x, y, color));
         }
+        return moves.into_iter()
+                    .filter(|(to_x, to_y)| !self.in_check_after_move(x, y, *to_x, *to_y, None, color))
+                    .collect()
     }
 
     pub fn get_player(&self) -> Color {
@@ -412,11 +897,44 @@ impl Game {
         self.board
     }
 
-    pub fn get_promotion_type(&self) -> Piece {
-        self.promotion_piece
+    // Returns the squares holding the given piece of the given color as a bitboard, laid out like the ones attacks takes
+    pub fn get_bitboard(&self, piece : Piece, color : Color) -> u64 {
+        self.pieces[piece_index(piece)] & self.color_bits(color)
+    }
+
+    // Returns every occupied square as a bitboard
+    pub fn get_occupancy(&self) -> u64 {
+        self.black | self.white
+    }
+
+    pub fn get_castling_rights(&self) -> CastlingRights {
+        self.castling
+    }
+
+    // Returns the number of moves made by either player since the last pawn move or capture
+    pub fn get_halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    // Returns the number of the current move, starting at 1 and incremented after every move by black
+    pub fn get_fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
+    // Returns every move played so far in Standard Algebraic Notation
+    pub fn get_san_moves(&self) -> &[String] {
+        &self.san_moves
+    }
+
+    // Returns the square a pawn can be captured on en passant in standard chess coordinates, if any
+    pub fn get_en_passant_square(&self) -> Option<String> {
+        self.en_passant.map(|(x, y)| Square::at(x, y).to_string())
     }
 }
 
+// The pieces a pawn can promote to
+const PROMOTION_PIECES : [Piece; 4] = [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight];
+
 // Returns the opposite of the given color
 fn get_opposite_color(color : Color) -> Color {
     match color {
@@ -426,25 +944,15 @@ fn get_opposite_color(color : Color) -> Color {
 }
 
 // Converts alphanumeric chess coordinates to numeric board coordinates
-pub fn string_to_coordinates(position: &str) -> (usize, usize) {
-    let mut x = position.chars().nth(0).unwrap();
-    x.make_ascii_uppercase();
-    let x = usize::from((x as u8) - 65); // Turn x into an integer by casting char to u8 and removing the ASCII offset
-
-    let y = position.chars().nth(1).unwrap();
-    let y = usize::from(8 - (y as u8 - 48)); // Same as x, but subtract from 8 to uninvert y coordinate
-
-    return (x, y)
+pub fn string_to_coordinates(position: &str) -> Result<(usize, usize), ParseSquareError> {
+    let square = position.parse::<Square>()?;
+    return Ok(square.coordinates())
 }
 
 // Returns a string representation of numeric board coordinates
-pub fn coordinates_to_string(x: usize, y: usize) -> String {
-    let x = u8::try_from(x).unwrap();
-    let x = (x + 65) as char;
-
-    let y = u8::try_from(y).unwrap();
-    let y = (8 - y + 48) as char;
-    return String::from(x) + &String::from(y)
+pub fn coordinates_to_string(x: usize, y: usize) -> Result<String, ParseSquareError> {
+    let square = Square::new(x, y)?;
+    return Ok(square.to_string())
 }
 
 // Creates a string representation of the current board
@@ -485,7 +993,9 @@ impl fmt::Display for Game {
     }
 }
 
+// The tests count their loops by hand and compare against owned strings
 #[cfg(test)]
+#[allow(clippy::explicit_counter_loop, clippy::cmp_owned)]
 mod tests {
     use super::*;
 
@@ -527,9 +1037,10 @@ mod tests {
         ];
         game.black = 0x00_00_10_00_00_00_00_00;
         game.white = 0x00_00_00_00_20_00_00_00;
+        game.sync_pieces();
         println!("{}", game);
         assert!(!game.is_in_check(Color::Black));
-        game.move_piece(2, 4, 2, 3);
+        game.move_piece(2, 4, 2, 3, None);
         println!("{}", game);
         assert!(game.is_in_check(Color::Black));
     }
@@ -549,14 +1060,40 @@ mod tests {
         ];
         game.black = 0x10_00_00_00_00_00_00_00;
         game.white = 0x80_80_00_00_00_00_00_00;
+        game.sync_pieces();
         println!("{}", game);
         assert!(game.is_in_check(Color::Black));
         assert!(game.has_no_moves(Color::Black));
-        game.move_piece(0, 1, 0, 4);
+        game.move_piece(0, 1, 0, 4, None);
         println!("{}", game);
         assert!(!game.has_no_moves(Color::Black));
     }
 
+    #[test]
+    fn attack_queries() {
+        // Double check from a bishop and a knight
+        let game = Game::from_fen("4k3/8/8/8/1b6/5n2/8/4K3 w - - 0 1").unwrap();
+        let square = |name : &str| name.parse::<Square>().unwrap();
+        assert!(game.find_king(Color::White) == Some(square("E1")));
+        assert!(game.is_in_check(Color::White) && !game.is_in_check(Color::Black));
+        assert!(game.checkers() == [square("B4"), square("F3")]);
+        assert!(game.attackers_of(square("D2")) == [square("B4"), square("F3"), square("E1")]);
+        assert!(game.is_square_attacked(square("D2"), Color::White));
+        assert!(game.is_square_attacked(square("E1"), Color::Black));
+        assert!(!game.is_square_attacked(square("A1"), Color::Black));
+        assert!(game.attackers_of(square("A8")).is_empty());
+
+        // A board without kings is not in check
+        let mut game = Game::new();
+        game.board = [[None; 8]; 8];
+        game.black = 0;
+        game.white = 0;
+        game.sync_pieces();
+        assert!(game.find_king(Color::White).is_none());
+        assert!(!game.is_in_check(Color::White));
+        assert!(game.checkers().is_empty());
+    }
+
     #[test]
     fn check_after_move() {
         let mut game = Game::new();
@@ -572,14 +1109,24 @@ mod tests {
         ];
         game.black = 0x00_00_10_00_00_00_00_00;
         game.white = 0x00_00_00_00_20_00_00_00;
+        game.sync_pieces();
         println!("{}", game);
         assert!(!game.is_in_check(Color::Black));
-        assert!(game.in_check_after_move(2, 4, 2, 3, Color::Black));
+        assert!(game.in_check_after_move(2, 4, 2, 3, None, Color::Black));
         let moves = game.get_possible_moves("D6").unwrap();
         println!("{:?}", moves)
 
     }
 
+    #[test]
+    fn check_after_underpromotion() {
+        // b8=N checks the king on d7 where a queen on b8 would not
+        let game = Game::from_fen("8/1P1k4/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(game.in_check_after_move(1, 1, 1, 0, Some(Piece::Knight), Color::Black));
+        assert!(!game.in_check_after_move(1, 1, 1, 0, Some(Piece::Queen), Color::Black));
+        assert!(!game.in_check_after_move(1, 1, 1, 0, Some(Piece::Rook), Color::Black));
+    }
+
     #[test]
     fn string_conversion() {
         let mut counter_x = 0;
@@ -589,10 +1136,10 @@ mod tests {
                 counter_y -= 1;
                 let coordinates = String::from(letter) + digit;
                 println!("Before conversion: {}", coordinates);
-                let coordinates = string_to_coordinates(&coordinates);
+                let coordinates = string_to_coordinates(&coordinates).unwrap();
                 println!("After conversion to number: {:?}", coordinates);
                 assert!(coordinates == (counter_x, counter_y));
-                let coordinates = coordinates_to_string(coordinates.0, coordinates.1);
+                let coordinates = coordinates_to_string(coordinates.0, coordinates.1).unwrap();
                 println!("After conversion back to string: {}", coordinates);
                 assert!(coordinates == String::from(letter) + digit);
             }
@@ -600,24 +1147,58 @@ mod tests {
         }
     }
 
+    // Control that malformed and out of bounds coordinates are rejected instead of panicking
+    #[test]
+    fn invalid_coordinates () {
+        let mut game = Game::new();
+        assert!(string_to_coordinates("Z9") == Err(ParseSquareError::InvalidFile('Z')));
+        assert!(string_to_coordinates("e") == Err(ParseSquareError::InvalidLength(1)));
+        assert!(string_to_coordinates("") == Err(ParseSquareError::InvalidLength(0)));
+        assert!(coordinates_to_string(8, 0) == Err(ParseSquareError::OutOfBounds(8, 0)));
+        assert!(game.get_possible_moves("I2") == Err(ParseSquareError::InvalidFile('I')));
+        assert!(game.get_possible_moves("E4") == Ok(vec![]));
+        assert!(game.make_move("E2", "E9").is_err());
+        assert!(game.make_move("", "E4").is_err());
+        assert!(game.get_color_at(8, 0).is_none());
+        assert!(game.get_color_at(0, 8).is_none());
+    }
+
+    #[test]
+    fn move_errors () {
+        let mut game = Game::from_fen("4kr2/8/8/8/1b6/8/3P4/4K3 w - - 0 1").unwrap();
+        assert!(game.make_move("E9", "E8") == Err(MoveError::InvalidSquare(ParseSquareError::InvalidRank('9'))));
+        assert!(game.make_move("E4", "E5") == Err(MoveError::EmptySquare));
+        assert!(game.make_move("E8", "E7") == Err(MoveError::WrongPlayer(Color::Black)));
+        assert!(game.make_move("E1", "E3") == Err(MoveError::IllegalDestination));
+        assert!(game.make_move("D2", "D3") == Err(MoveError::PinnedPiece));
+        assert!(game.make_move("E1", "F1") == Err(MoveError::KingIntoCheck));
+
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/3P4/r3K3 w - - 0 1").unwrap();
+        assert!(game.make_move("D2", "D3") == Err(MoveError::StillInCheck));
+        assert!(game.make_move("E1", "D1") == Err(MoveError::StillInCheck));
+        assert!(game.make_move("E1", "E2").is_ok());
+        game.resign();
+        assert!(game.make_move("E8", "E7") == Err(MoveError::GameOver(GameState::GameOver)));
+    }
+
     // Play a scholar's mate to demonstrate chessy behavior
     #[test]
     fn scholars_mate () {
         let mut game = Game::new();
         println!("{}", game);
-        assert!(game.make_move("E2", "E4") != None);
+        assert!(game.make_move("E2", "E4").is_ok());
         println!("{}", game);
-        assert!(game.make_move("E7", "E5") != None);
+        assert!(game.make_move("E7", "E5").is_ok());
         println!("{}", game);
-        assert!(game.make_move("D1", "H5") != None);
+        assert!(game.make_move("D1", "H5").is_ok());
         println!("{}", game);
-        assert!(game.make_move("B8", "C6") != None);
+        assert!(game.make_move("B8", "C6").is_ok());
         println!("{}", game);
-        assert!(game.make_move("F1", "C4") != None);
+        assert!(game.make_move("F1", "C4").is_ok());
         println!("{}", game);
-        assert!(game.make_move("G8", "F6") != None);
+        assert!(game.make_move("G8", "F6").is_ok());
         println!("{}", game);
-        assert!(game.make_move("H5", "F7") != None);
+        assert!(game.make_move("H5", "F7").is_ok());
         println!("{}", game);
         assert!(game.get_game_state() == GameState::Checkmate)
         
@@ -628,17 +1209,20 @@ mod tests {
     fn possible_moves () {
         // Move a white pawn to check the black king and control that capturing works as expected
         let mut game = Game::new();
-        game.move_piece(2, 6, 2, 2);
+        game.move_piece(2, 6, 2, 2, None);
         println!("{}", game);
-        assert!(game.get_possible_moves("C6") == Some(vec![String::from("D7"), String::from("B7")]));
-        assert!(game.make_move("C6", "D7") != None);
+        // The order of the moves follows the move generation, so compare them sorted
+        let mut moves = game.get_possible_moves("C6").unwrap();
+        moves.sort();
+        assert!(moves == vec![String::from("B7"), String::from("D7")]);
+        assert!(game.make_move("C6", "D7").is_ok());
         println!("{}", game);
         assert!(game.state == GameState::Check);
         // Control that the expected pieces can capture the pawn
-        assert!(game.get_possible_moves("B8") == Some(vec![String::from("D7")]));
-        assert!(game.get_possible_moves("C8") == Some(vec![String::from("D7")]));
-        assert!(game.get_possible_moves("D8") == Some(vec![String::from("D7")]));
-        assert!(game.get_possible_moves("D8") == Some(vec![String::from("D7")]));
+        assert!(game.get_possible_moves("B8") == Ok(vec![String::from("D7")]));
+        assert!(game.get_possible_moves("C8") == Ok(vec![String::from("D7")]));
+        assert!(game.get_possible_moves("D8") == Ok(vec![String::from("D7")]));
+        assert!(game.get_possible_moves("D8") == Ok(vec![String::from("D7")]));
         // Control that the _only_ valid moves are the ones that end the check
         let mut all_black_moves : Vec<String>;
         all_black_moves = game.get_possible_moves("E8").unwrap();
@@ -673,32 +1257,461 @@ mod tests {
             [Some(Piece::Rook), Some(Piece::Knight), Some(Piece::Bishop), Some(Piece::Queen), Some(Piece::King), Some(Piece::Bishop), Some(Piece::Knight), Some(Piece::Rook)]
         ];
         game.black = 0x01_00_00_00_00_00_00_00;
-        assert!(game.make_move("A2", "A3") != None);
+        game.sync_pieces();
+        assert!(game.make_move("A2", "A3").is_ok());
         game.player = Color::White;
-        assert!(game.make_move("A3", "A4") != None);
+        game.sync_pieces();
+        assert!(game.make_move("A3", "A4").is_ok());
         game.player = Color::White;
-        assert!(game.make_move("A4", "A5") != None);
+        game.sync_pieces();
+        assert!(game.make_move("A4", "A5").is_ok());
         game.player = Color::White;
-        assert!(game.make_move("A5", "A6") != None);
+        game.sync_pieces();
+        assert!(game.make_move("A5", "A6").is_ok());
         game.player = Color::White;
-        assert!(game.make_move("A6", "A7") != None);
+        game.sync_pieces();
+        assert!(game.make_move("A6", "A7").is_ok());
         game.player = Color::White;
-        assert!(game.make_move("A7", "A8") != None);
+        game.sync_pieces();
+        assert!(game.get_possible_moves("A7") == Ok(vec![
+            String::from("A8=Q"),
+            String::from("A8=R"),
+            String::from("A8=B"),
+            String::from("A8=N")
+            ]));
+        assert!(game.make_move("A7", "A8") == Err(MoveError::MissingPromotion));
+        assert!(game.make_move("A7", "A8=K") == Err(MoveError::InvalidPromotion(Piece::King)));
+        assert!(game.make_move("A7", "A8=X") == Err(MoveError::UnknownPromotion(String::from("X"))));
+        assert!(game.make_move("a7", "a8=x") == Err(MoveError::UnknownPromotion(String::from("x"))));
+        assert!(game.make_move("a7", "a8=k") == Err(MoveError::InvalidPromotion(Piece::King)));
+        assert!(game.make_move("A7", "A8=") == Err(MoveError::UnknownPromotion(String::new())));
+        assert!(game.make_move("B2", "B3=Q") == Err(MoveError::InvalidPromotion(Piece::Queen)));
+        assert!(game.make_move("A7", "A8=Q").is_ok());
         assert!(game.board[0][0] == Some(Piece::Queen));
 
-        game.set_promotion(Piece::Knight);
         game.player = Color::White;
-        assert!(game.make_move("B2", "B3") != None);
+        game.sync_pieces();
+        assert!(game.make_move("B2", "B3").is_ok());
         game.player = Color::White;
-        assert!(game.make_move("B3", "B4") != None);
+        game.sync_pieces();
+        assert!(game.make_move("B3", "B4").is_ok());
         game.player = Color::White;
-        assert!(game.make_move("B4", "B5") != None);
+        game.sync_pieces();
+        assert!(game.make_move("B4", "B5").is_ok());
         game.player = Color::White;
-        assert!(game.make_move("B5", "B6") != None);
+        game.sync_pieces();
+        assert!(game.make_move("B5", "B6").is_ok());
         game.player = Color::White;
-        assert!(game.make_move("B6", "B7") != None);
+        game.sync_pieces();
+        assert!(game.make_move("B6", "B7").is_ok());
         game.player = Color::White;
-        assert!(game.make_move("B7", "B8") != None);
+        game.sync_pieces();
+        // The promotion letter can be lowercase, like the coordinates
+        assert!(game.make_move("b7", "b8=n").is_ok());
         assert!(game.board[0][1] == Some(Piece::Knight))
     }
+
+    #[test]
+    fn castling_king_side () {
+        let mut game = Game::new();
+        assert!(game.make_move("E2", "E4").is_ok());
+        assert!(game.make_move("E7", "E5").is_ok());
+        assert!(game.make_move("G1", "F3").is_ok());
+        assert!(game.make_move("B8", "C6").is_ok());
+        assert!(!game.get_possible_moves("E1").unwrap().contains(&String::from("G1")));
+        assert!(game.make_move("F1", "C4").is_ok());
+        assert!(game.make_move("F8", "C5").is_ok());
+        assert!(game.get_possible_moves("E1").unwrap().contains(&String::from("G1")));
+        assert!(game.make_move("E1", "G1").is_ok());
+        println!("{}", game);
+        assert!(game.board[7][6] == Some(Piece::King));
+        assert!(game.board[7][5] == Some(Piece::Rook));
+        assert!(game.board[7][7].is_none());
+        assert!(game.get_color_at(5, 7) == Some(Color::White));
+        assert!(game.get_color_at(7, 7).is_none());
+        assert!(!game.get_castling_rights().king_side(Color::White));
+        assert!(!game.get_castling_rights().queen_side(Color::White));
+        assert!(game.get_castling_rights().king_side(Color::Black));
+    }
+
+    #[test]
+    fn castling_rules () {
+        let mut game = Game::new();
+        game.board = [
+            [None, None, None, None, Some(Piece::King), Some(Piece::Rook), None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [Some(Piece::Rook), None, None, None, Some(Piece::King), None, None, Some(Piece::Rook)],
+        ];
+        game.black = 0x0C_00_00_00_00_00_00_00;
+        game.white = 0x00_00_00_00_00_00_00_89;
+        game.sync_pieces();
+        println!("{}", game);
+        // The black rook attacks F1, so the king may not pass through it
+        let moves = game.get_possible_moves("E1").unwrap();
+        assert!(!moves.contains(&String::from("G1")));
+        assert!(moves.contains(&String::from("C1")));
+        assert!(game.make_move("E1", "C1").is_ok());
+        assert!(game.board[7][2] == Some(Piece::King));
+        assert!(game.board[7][3] == Some(Piece::Rook));
+        assert!(game.board[7][0].is_none());
+
+        // Castling out of check is not allowed
+        let mut game = Game::new();
+        game.board = [
+            [None, None, None, None, Some(Piece::King), None, None, None],
+            [None, None, None, None, Some(Piece::Rook), None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [Some(Piece::Rook), None, None, None, Some(Piece::King), None, None, Some(Piece::Rook)],
+        ];
+        game.black = 0x08_08_00_00_00_00_00_00;
+        game.white = 0x00_00_00_00_00_00_00_89;
+        game.sync_pieces();
+        let moves = game.get_possible_moves("E1").unwrap();
+        assert!(!moves.contains(&String::from("G1")));
+        assert!(!moves.contains(&String::from("C1")));
+    }
+
+    #[test]
+    fn castling_rights_lost () {
+        let mut game = Game::new();
+        game.board = [
+            [Some(Piece::Rook), None, None, None, Some(Piece::King), None, Some(Piece::Knight), Some(Piece::Rook)],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [Some(Piece::Rook), None, None, None, Some(Piece::King), None, None, Some(Piece::Rook)],
+        ];
+        game.black = 0x8B_00_00_00_00_00_00_00;
+        game.white = 0x00_00_00_00_00_00_00_89;
+        game.sync_pieces();
+        // Capturing the rook on H8 removes black's king side right
+        assert!(game.make_move("H1", "H8").is_ok());
+        assert!(!game.get_castling_rights().white_king_side);
+        assert!(!game.get_castling_rights().black_king_side);
+        assert!(game.get_castling_rights().black_queen_side);
+        // Moving the rook away and back does not restore the right
+        assert!(game.make_move("A8", "A7").is_ok());
+        assert!(game.make_move("A1", "A2").is_ok());
+        assert!(game.make_move("A7", "A8").is_ok());
+        assert!(!game.get_castling_rights().black_queen_side);
+        assert!(!game.get_possible_moves("E8").unwrap().contains(&String::from("C8")));
+    }
+
+    #[test]
+    fn en_passant () {
+        let mut game = Game::new();
+        assert!(game.make_move("E2", "E4").is_ok());
+        assert!(game.get_en_passant_square() == Some(String::from("E3")));
+        assert!(game.make_move("A7", "A6").is_ok());
+        assert!(game.get_en_passant_square().is_none());
+        assert!(game.make_move("E4", "E5").is_ok());
+        assert!(game.make_move("D7", "D5").is_ok());
+        assert!(game.get_possible_moves("E5").unwrap().contains(&String::from("D6")));
+        assert!(game.make_move("E5", "D6").is_ok());
+        println!("{}", game);
+        assert!(game.board[2][3] == Some(Piece::Pawn));
+        assert!(game.board[3][3].is_none());
+        assert!(game.get_color_at(3, 3).is_none());
+
+        // The right to capture en passant expires after one move
+        let mut game = Game::new();
+        assert!(game.make_move("E2", "E4").is_ok());
+        assert!(game.make_move("A7", "A6").is_ok());
+        assert!(game.make_move("E4", "E5").is_ok());
+        assert!(game.make_move("F7", "F5").is_ok());
+        assert!(game.make_move("H2", "H3").is_ok());
+        assert!(game.make_move("A6", "A5").is_ok());
+        assert!(!game.get_possible_moves("E5").unwrap().contains(&String::from("F6")));
+        assert!(game.make_move("E5", "F6").is_err());
+    }
+
+    #[test]
+    fn en_passant_horizontal_pin () {
+        let mut game = Game::new();
+        game.board = [
+            [None, None, None, None, Some(Piece::King), None, None, None],
+            [None, None, Some(Piece::Pawn), None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [Some(Piece::King), Some(Piece::Pawn), None, None, None, None, None, Some(Piece::Rook)],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+        ];
+        game.black = 0x08_20_00_01_00_00_00_00;
+        game.white = 0x00_00_00_C0_00_00_00_00;
+        game.player = Color::Black;
+        game.sync_pieces();
+        assert!(game.make_move("C7", "C5").is_ok());
+        // Capturing would remove both pawns from the fifth rank and expose the king to the rook
+        assert!(!game.get_possible_moves("B5").unwrap().contains(&String::from("C6")));
+        assert!(game.make_move("B5", "C6").is_err());
+    }
+
+    #[test]
+    fn stalemate () {
+        let mut game = Game::new();
+        game.board = [
+            [Some(Piece::King), None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, Some(Piece::King), None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, Some(Piece::Queen), None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+        ];
+        game.black = 0x80_00_00_00_00_00_00_00;
+        game.white = 0x00_00_20_00_00_00_40_00;
+        game.sync_pieces();
+        assert!(game.make_move("B2", "B6").map(|outcome| outcome.state) == Ok(GameState::Stalemate));
+        assert!(game.get_game_state() == GameState::Stalemate);
+        assert!(game.get_player() == Color::Black);
+        assert!(game.get_possible_moves("A8") == Ok(vec![]));
+        // No further moves are accepted, not even from the side that just moved
+        assert!(game.make_move("A8", "B8").is_err());
+        game.player = Color::White;
+        game.sync_pieces();
+        assert!(game.make_move("B6", "B2").is_err());
+    }
+
+    #[test]
+    fn resign () {
+        let mut game = Game::new();
+        assert!(game.make_move("E2", "E4").is_ok());
+        assert!(game.resign() == Some(GameState::GameOver));
+        assert!(game.get_player() == Color::Black);
+        assert!(game.make_move("E7", "E5").is_err());
+        assert!(game.resign().is_none());
+    }
+
+    #[test]
+    fn threefold_repetition () {
+        let mut game = Game::new();
+        assert!(game.claim_draw().is_none());
+        for _i in 0..2 {
+            assert!(game.make_move("G1", "F3").is_ok());
+            assert!(game.make_move("G8", "F6").is_ok());
+            assert!(game.can_claim_draw().is_none());
+            assert!(game.make_move("F3", "G1").is_ok());
+            assert!(game.make_move("F6", "G8").is_ok());
+        }
+        // The starting position has now occurred three times
+        assert!(game.can_claim_draw() == Some(DrawReason::ThreefoldRepetition));
+        assert!(game.claim_draw() == Some(GameState::Draw(DrawReason::ThreefoldRepetition)));
+        assert!(game.make_move("G1", "F3").is_err());
+    }
+
+    #[test]
+    fn fivefold_repetition () {
+        let mut game = Game::new();
+        for _i in 0..3 {
+            assert!(game.make_move("G1", "F3").map(|outcome| outcome.state) == Ok(GameState::InProgress));
+            assert!(game.make_move("G8", "F6").map(|outcome| outcome.state) == Ok(GameState::InProgress));
+            assert!(game.make_move("F3", "G1").map(|outcome| outcome.state) == Ok(GameState::InProgress));
+            assert!(game.make_move("F6", "G8").map(|outcome| outcome.state) == Ok(GameState::InProgress));
+        }
+        assert!(game.make_move("G1", "F3").map(|outcome| outcome.state) == Ok(GameState::InProgress));
+        assert!(game.make_move("G8", "F6").map(|outcome| outcome.state) == Ok(GameState::InProgress));
+        assert!(game.make_move("F3", "G1").map(|outcome| outcome.state) == Ok(GameState::InProgress));
+        assert!(game.make_move("F6", "G8").map(|outcome| outcome.state) == Ok(GameState::Draw(DrawReason::FivefoldRepetition)));
+        assert!(game.make_move("G1", "F3").is_err());
+    }
+
+    #[test]
+    fn repetition_ignores_impossible_en_passant () {
+        // After 1. e4 the en passant square is set, but no black pawn can capture on it,
+        // so the position after 1. e4 Nf6 2. Nf3 Ng8 3. Ng1 is the same as after 1. e4
+        let mut game = Game::new();
+        assert!(game.make_move("E2", "E4").is_ok());
+        assert!(game.make_move("G8", "F6").is_ok());
+        assert!(game.make_move("G1", "F3").is_ok());
+        assert!(game.make_move("F6", "G8").is_ok());
+        assert!(game.make_move("F3", "G1").is_ok());
+        assert!(game.repetition_count() == 2);
+    }
+
+    #[test]
+    fn move_rules () {
+        let mut game = Game::new();
+        game.board = [
+            [Some(Piece::King), None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, Some(Piece::Pawn)],
+            [None, None, None, None, None, None, Some(Piece::Rook), Some(Piece::King)],
+        ];
+        game.black = 0x80_00_00_00_00_00_00_00;
+        game.white = 0x00_00_00_00_00_00_01_03;
+        game.sync_pieces();
+        game.halfmove_clock = 98;
+        assert!(game.make_move("G1", "G2").is_ok());
+        assert!(game.can_claim_draw().is_none());
+        assert!(game.make_move("A8", "B8").is_ok());
+        assert!(game.get_halfmove_clock() == 100);
+        assert!(game.can_claim_draw() == Some(DrawReason::FiftyMoveRule));
+        // A pawn move resets the clock
+        assert!(game.make_move("H2", "H3").is_ok());
+        assert!(game.get_halfmove_clock() == 0);
+        assert!(game.can_claim_draw().is_none());
+
+        game.halfmove_clock = 148;
+        assert!(game.make_move("B8", "A8").map(|outcome| outcome.state) == Ok(GameState::InProgress));
+        assert!(game.make_move("G2", "G1").map(|outcome| outcome.state) == Ok(GameState::Draw(DrawReason::SeventyFiveMoveRule)));
+        assert!(game.make_move("A8", "B8").is_err());
+    }
+
+    #[test]
+    fn insufficient_material () {
+        let mut game = Game::new();
+        assert!(!game.is_insufficient_material(Color::White));
+        assert!(!game.is_insufficient_material(Color::Black));
+
+        // King and bishop against king and bishop on the same shade can not be won by either side
+        game.board = [
+            [None, None, None, None, Some(Piece::King), None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, Some(Piece::Bishop), None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, Some(Piece::King), Some(Piece::Bishop), None, None],
+        ];
+        game.black = 0x08_00_20_00_00_00_00_00;
+        game.white = 0x00_00_00_00_00_00_00_0C;
+        game.sync_pieces();
+        assert!(game.is_insufficient_material(Color::White));
+        assert!(game.is_insufficient_material(Color::Black));
+
+        // On opposite shades a helpmate is possible
+        game.board[7][5] = None;
+        game.board[7][2] = Some(Piece::Bishop);
+        game.white = 0x00_00_00_00_00_00_00_28;
+        game.sync_pieces();
+        assert!(!game.is_insufficient_material(Color::White));
+        assert!(!game.is_insufficient_material(Color::Black));
+
+        // A knight can only mate with help from the opponent's pieces
+        game.board[7][2] = Some(Piece::Knight);
+        game.sync_pieces();
+        assert!(!game.is_insufficient_material(Color::White));
+        game.board[2][2] = None;
+        game.black = 0x08_00_00_00_00_00_00_00;
+        game.sync_pieces();
+        assert!(game.is_insufficient_material(Color::White));
+        assert!(game.is_insufficient_material(Color::Black));
+
+        // A single pawn is enough for the side that has it, since it can promote, but a lone king never is
+        game.board[6][0] = Some(Piece::Pawn);
+        game.white = 0x00_00_00_00_00_00_80_28;
+        game.sync_pieces();
+        assert!(!game.is_insufficient_material(Color::White));
+        assert!(game.is_insufficient_material(Color::Black));
+    }
+
+    #[test]
+    fn insufficient_material_draw () {
+        let mut game = Game::new();
+        game.board = [
+            [None, None, None, None, Some(Piece::King), None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, None, None, None, None, None, None, None],
+            [None, Some(Piece::Knight), None, None, None, None, None, None],
+            [None, None, None, Some(Piece::Rook), Some(Piece::King), None, None, None],
+        ];
+        game.black = 0x08_00_00_00_00_00_40_00;
+        game.white = 0x00_00_00_00_00_00_00_18;
+        game.player = Color::Black;
+        game.sync_pieces();
+        assert!(game.make_move("B2", "C4").map(|outcome| outcome.state) == Ok(GameState::InProgress));
+        assert!(game.make_move("D1", "D2").map(|outcome| outcome.state) == Ok(GameState::InProgress));
+        // Capturing the rook leaves king and knight against king
+        assert!(game.make_move("C4", "D2").map(|outcome| outcome.state) == Ok(GameState::Draw(DrawReason::InsufficientMaterial)));
+        assert!(game.make_move("E1", "D2").is_err());
+    }
+
+    #[test]
+    fn legal_captures () {
+        assert!(Game::new().legal_captures().is_empty());
+        // Kiwipete has 8 captures, matching the Chess Programming Wiki's perft results
+        let game = Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let captures = game.legal_captures();
+        assert!(captures.len() == 8);
+        assert!(captures.iter().all(|chess_move| chess_move.flags.capture));
+        let all_captures = game.legal_moves().into_iter().filter(|chess_move| chess_move.flags.capture).count();
+        assert!(all_captures == captures.len());
+
+        // Promotions count even without a capture, and en passant is a capture
+        let game = Game::from_fen("4k3/1P6/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let captures = game.legal_captures();
+        assert!(captures.len() == 5);
+        assert!(captures.iter().filter(|chess_move| chess_move.promotion.is_some()).count() == 4);
+        assert!(captures.contains(&Move::from_uci("e5d6").unwrap()));
+    }
+
+    #[test]
+    fn legal_moves () {
+        let game = Game::new();
+        let moves = game.legal_moves();
+        assert!(moves.len() == 20);
+        assert!(moves.contains(&Move::new((6, 7), (5, 5), None)));
+        assert!(moves.iter().filter(|chess_move| chess_move.flags.double_push).count() == 8);
+
+        let mut game = Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let moves = game.legal_moves();
+        let find = |moves : &[Move], from : (usize, usize), to : (usize, usize)| *moves.iter().find(|chess_move| chess_move.from == from && chess_move.to == to).unwrap();
+        assert!(find(&moves, (4, 7), (6, 7)).flags == MoveFlags { castle: true, ..MoveFlags::default() });
+        assert!(find(&moves, (4, 7), (2, 7)).flags.castle);
+        assert!(find(&moves, (4, 3), (5, 1)).flags == MoveFlags { capture: true, ..MoveFlags::default() });
+        assert!(find(&moves, (0, 6), (0, 4)).flags == MoveFlags { double_push: true, ..MoveFlags::default() });
+        assert!(find(&moves, (0, 6), (0, 5)).flags == MoveFlags::default());
+        // After a4 black can take en passant
+        assert!(game.make_move("A2", "A4").is_ok());
+        let moves = game.legal_moves();
+        assert!(find(&moves, (1, 4), (0, 5)).flags == MoveFlags { capture: true, en_passant: true, ..MoveFlags::default() });
+
+        let game = Game::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let promotions = game.legal_moves().into_iter().filter(|chess_move| chess_move.from == (1, 1)).collect::<Vec<Move>>();
+        assert!(promotions == [
+            Move::new((1, 1), (1, 0), Some(Piece::Queen)),
+            Move::new((1, 1), (1, 0), Some(Piece::Rook)),
+            Move::new((1, 1), (1, 0), Some(Piece::Bishop)),
+            Move::new((1, 1), (1, 0), Some(Piece::Knight))
+        ]);
+    }
+
+    #[test]
+    fn play () {
+        let mut game = Game::from_fen("r3k3/1P6/8/8/8/8/p7/4K3 w - - 0 1").unwrap();
+        assert!(game.play(Move::new((1, 1), (1, 0), Some(Piece::King))) == Err(MoveError::InvalidPromotion(Piece::King)));
+        assert!(game.play(Move::new((4, 7), (4, 6), Some(Piece::Queen))) == Err(MoveError::InvalidPromotion(Piece::Queen)));
+        assert!(game.play(Move::new((4, 7), (4, 8), None)) == Err(MoveError::InvalidSquare(ParseSquareError::OutOfBounds(4, 8))));
+        assert!(game.play(Move::new((0, 0), (0, 1), None)) == Err(MoveError::WrongPlayer(Color::Black)));
+        let outcome = game.play(Move::new((1, 1), (0, 0), Some(Piece::Knight))).unwrap();
+        assert!(outcome.state == GameState::InProgress);
+        assert!(outcome.captured == Some(Piece::Rook));
+        assert!(outcome.chess_move.flags.capture);
+        assert!(outcome.san == "bxa8=N");
+        assert!(game.get_board()[0][0] == Some(Piece::Knight));
+        assert!(game.get_san_moves() == ["bxa8=N"]);
+    }
 }
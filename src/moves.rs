@@ -0,0 +1,274 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use crate::{Color, Game, GameState, ParseSquareError, Piece, Square};
+
+// A move from one square to another, with the piece a pawn promotes to if it reaches the last rank
+// Coordinates are numerical like everywhere else, with (0, 0) being the top left corner A8
+// The null move, which passes the turn, is represented by a move from a square to itself
+// Moves compare equal if they have the same squares and promotion, regardless of their flags
+#[derive(Clone, Copy, Debug)]
+pub struct Move {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    pub promotion: Option<Piece>,
+    pub flags: MoveFlags // Only filled in for moves that come from a Game, such as Game::legal_moves
+}
+
+// What kind of move a Move is, as worked out from the position it is played in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MoveFlags {
+    pub capture: bool, // Includes en passant
+    pub castle: bool,
+    pub en_passant: bool,
+    pub double_push: bool // A pawn moving two squares from its starting position
+}
+
+impl PartialEq for Move {
+    fn eq(&self, other : &Move) -> bool {
+        self.from == other.from && self.to == other.to && self.promotion == other.promotion
+    }
+}
+
+impl Eq for Move {}
+
+impl Hash for Move {
+    fn hash<H: Hasher>(&self, state : &mut H) -> () {
+        self.from.hash(state);
+        self.to.hash(state);
+        self.promotion.hash(state);
+    }
+}
+
+// The result of a move that was played
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MoveOutcome {
+    pub chess_move: Move, // The move as played, with its flags and promotion piece filled in
+    pub captured: Option<Piece>, // The piece taken by the move, if any
+    pub san: String, // The move in Standard Algebraic Notation, including any check or checkmate suffix
+    pub state: GameState // The game state after the move
+}
+
+// Reasons a move could not be played
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MoveError {
+    InvalidSquare(ParseSquareError), // A coordinate did not name a square on the board
+    GameOver(GameState), // The game has already ended in the given state
+    EmptySquare, // There is no piece on the starting square
+    WrongPlayer(Color), // The piece on the starting square belongs to the given player, who is not to move
+    IllegalDestination, // The piece can not move to the destination, regardless of check
+    PinnedPiece, // Moving the piece would expose its own king to an attack
+    KingIntoCheck, // The king would move onto an attacked square
+    StillInCheck, // The player is in check and the move does not get them out of it
+    MissingPromotion, // A pawn reaching the last rank has to be given a piece to promote to
+    UnknownPromotion(String), // The text after the '=' was not the letter of a piece
+    InvalidPromotion(Piece) // The given piece can not be promoted to, or the move is not a promotion
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::InvalidSquare(error) => {write!(f, "{}", error)},
+            MoveError::GameOver(state) => {write!(f, "the game is over ({:?})", state)},
+            MoveError::EmptySquare => {write!(f, "there is no piece to move")},
+            MoveError::WrongPlayer(color) => {write!(f, "it is not {:?}'s turn", color)},
+            MoveError::IllegalDestination => {write!(f, "the piece can not move there")},
+            MoveError::PinnedPiece => {write!(f, "the piece is pinned to its king")},
+            MoveError::KingIntoCheck => {write!(f, "the king can not move into check")},
+            MoveError::StillInCheck => {write!(f, "the move does not get out of check")},
+            MoveError::MissingPromotion => {write!(f, "a piece to promote to is required")},
+            MoveError::UnknownPromotion(text) => {write!(f, "unknown promotion piece \"{}\"", text)},
+            MoveError::InvalidPromotion(piece) => {write!(f, "can not promote to {:?} with this move", piece)}
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+impl From<ParseSquareError> for MoveError {
+    fn from(error : ParseSquareError) -> MoveError {
+        MoveError::InvalidSquare(error)
+    }
+}
+
+// Reasons a move in UCI long algebraic notation could not be read or played
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UciError {
+    InvalidLength(usize), // A UCI move is "0000" or 4 characters, plus one for a promotion
+    InvalidSquare(String), // A square was not a lowercase file a-h followed by a rank 1-8
+    InvalidPromotion(char), // The promotion letter was not one of q, r, b or n
+    SameSquare(String), // The move starts and ends on the same square, which only the null move "0000" may
+    IllegalMove(String), // The null move was given where a real move is needed
+    InvalidMove(String, MoveError) // The move was read but could not be played, for the reason in the MoveError
+}
+
+impl fmt::Display for UciError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UciError::InvalidLength(length) => {write!(f, "a UCI move can not be {} characters long", length)},
+            UciError::InvalidSquare(square) => {write!(f, "invalid square \"{}\"", square)},
+            UciError::InvalidPromotion(letter) => {write!(f, "invalid promotion '{}'", letter)},
+            UciError::SameSquare(uci) => {write!(f, "\"{}\" does not leave its square, the null move is \"0000\"", uci)},
+            UciError::IllegalMove(uci) => {write!(f, "\"{}\" is not a legal move", uci)},
+            UciError::InvalidMove(uci, error) => {write!(f, "\"{}\" can not be played: {}", uci, error)}
+        }
+    }
+}
+
+impl std::error::Error for UciError {}
+
+impl Move {
+    // The null move, written "0000" in UCI
+    pub const NULL: Move = Move {
+        from: (0, 0),
+        to: (0, 0),
+        promotion: None,
+        flags: MoveFlags { capture: false, castle: false, en_passant: false, double_push: false }
+    };
+
+    // Constructs a move without flags
+    pub fn new(from : (usize, usize), to : (usize, usize), promotion : Option<Piece>) -> Move {
+        Move { from, to, promotion, flags: MoveFlags::default() }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.from == self.to
+    }
+
+    // Parses a move in UCI long algebraic notation, like "e2e4", "e7e8q", "e1g1" or "0000"
+    pub fn from_uci(uci : &str) -> Result<Move, UciError> {
+        if uci == "0000" {
+            return Ok(Move::NULL);
+        }
+        if !uci.is_ascii() || (uci.len() != 4 && uci.len() != 5) {
+            return Err(UciError::InvalidLength(uci.chars().count()));
+        }
+        let from = parse_square(&uci[0..2])?;
+        let to = parse_square(&uci[2..4])?;
+        // A move to its own square would be written back as the null move
+        if from == to {
+            return Err(UciError::SameSquare(uci.to_string()));
+        }
+        let promotion = match uci[4..].chars().next() {
+            Some('q') => {Some(Piece::Queen)},
+            Some('r') => {Some(Piece::Rook)},
+            Some('b') => {Some(Piece::Bishop)},
+            Some('n') => {Some(Piece::Knight)},
+            Some(letter) => {return Err(UciError::InvalidPromotion(letter))},
+            None => {None}
+        };
+        return Ok(Move::new(from, to, promotion))
+    }
+
+    // Returns the move in UCI long algebraic notation
+    // Returns why not if a coordinate is off the board, since the fields can be set to anything
+    pub fn to_uci(&self) -> Result<String, ParseSquareError> {
+        let from = Square::new(self.from.0, self.from.1)?;
+        let to = Square::new(self.to.0, self.to.1)?;
+        if self.is_null() {
+            return Ok(String::from("0000"));
+        }
+        let mut uci = from.to_string() + &to.to_string();
+        uci.make_ascii_lowercase();
+        match self.promotion {
+            Some(Piece::Queen) => {uci.push('q')},
+            Some(Piece::Rook) => {uci.push('r')},
+            Some(Piece::Bishop) => {uci.push('b')},
+            Some(Piece::Knight) => {uci.push('n')},
+            Some(Piece::King) | Some(Piece::Pawn) | None => {}
+        }
+        return Ok(uci)
+    }
+}
+
+impl FromStr for Move {
+    type Err = UciError;
+
+    fn from_str(uci : &str) -> Result<Move, UciError> {
+        Move::from_uci(uci)
+    }
+}
+
+// Writes the move in UCI long algebraic notation
+// A move with a coordinate off the board has no UCI notation, so its numerical coordinates are written instead
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_uci() {
+            Ok(uci) => {write!(f, "{}", uci)},
+            Err(_) => {write!(f, "{:?}-{:?}", self.from, self.to)}
+        }
+    }
+}
+
+// Parses a UCI square into numerical coordinates, which unlike other squares has to be lowercase
+fn parse_square(square : &str) -> Result<(usize, usize), UciError> {
+    let invalid = || UciError::InvalidSquare(square.to_string());
+    if square.chars().any(|character| character.is_ascii_uppercase()) {
+        return Err(invalid());
+    }
+    return square.parse::<Square>().map(|square| square.coordinates()).map_err(|_| invalid())
+}
+
+impl Game {
+    // Makes a move given in UCI long algebraic notation and returns the resulting game state
+    // Promotions have to name their piece, as in "e7e8q"
+    // The null move "0000" is not a legal move and is rejected
+    pub fn make_uci_move(&mut self, uci : &str) -> Result<GameState, UciError> {
+        let chess_move = Move::from_uci(uci)?;
+        if chess_move.is_null() {
+            return Err(UciError::IllegalMove(uci.to_string()));
+        }
+        return self.play(chess_move).map(|outcome| outcome.state).map_err(|error| UciError::InvalidMove(uci.to_string(), error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_format() {
+        for (uci, expected) in [
+            ("e2e4", Move::new((4, 6), (4, 4), None)),
+            ("e7e8q", Move::new((4, 1), (4, 0), Some(Piece::Queen))),
+            ("a2a1n", Move::new((0, 6), (0, 7), Some(Piece::Knight))),
+            ("e1g1", Move::new((4, 7), (6, 7), None)),
+            ("h8a1", Move::new((7, 0), (0, 7), None)),
+            ("0000", Move::NULL)
+        ] {
+            let parsed = uci.parse::<Move>().unwrap();
+            assert!(parsed == expected);
+            assert!(parsed.to_string() == uci);
+        }
+        assert!(Move::NULL.is_null());
+        assert!(Move::from_uci("") == Err(UciError::InvalidLength(0)));
+        assert!(Move::from_uci("e2e4qq") == Err(UciError::InvalidLength(6)));
+        assert!(Move::from_uci("e2é4") == Err(UciError::InvalidLength(4)));
+        assert!(Move::from_uci("E2E4") == Err(UciError::InvalidSquare(String::from("E2"))));
+        assert!(Move::from_uci("e2e9") == Err(UciError::InvalidSquare(String::from("e9"))));
+        assert!(Move::from_uci("e7e8k") == Err(UciError::InvalidPromotion('k')));
+        // Coordinates off the board are reported instead of panicking
+        assert!(Move::new((8, 0), (4, 4), None).to_uci() == Err(ParseSquareError::OutOfBounds(8, 0)));
+        assert!(Move::new((4, 6), (4, 9), None).to_string() == "(4, 6)-(4, 9)");
+        assert!(Move::new((4, 6), (4, 4), None).to_uci() == Ok(String::from("e2e4")));
+        // Only "0000" is the null move
+        assert!(Move::from_uci("e2e2") == Err(UciError::SameSquare(String::from("e2e2"))));
+        assert!(Move::from_uci("a1a1q") == Err(UciError::SameSquare(String::from("a1a1q"))));
+    }
+
+    #[test]
+    fn make_uci_move() {
+        let mut game = Game::from_fen("r3k3/1P6/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert!(game.make_uci_move("0000") == Err(UciError::IllegalMove(String::from("0000"))));
+        assert!(game.make_uci_move("b7b8") == Err(UciError::InvalidMove(String::from("b7b8"), MoveError::MissingPromotion)));
+        assert!(game.make_uci_move("e1e2q") == Err(UciError::InvalidMove(String::from("e1e2q"), MoveError::InvalidPromotion(Piece::Queen))));
+        assert!(game.make_uci_move("e1c1") == Err(UciError::InvalidMove(String::from("e1c1"), MoveError::IllegalDestination)));
+        assert!(game.make_uci_move("b7a8n") == Ok(GameState::InProgress));
+        assert!(game.get_board()[0][0] == Some(Piece::Knight));
+        assert!(game.make_uci_move("e8e7") == Ok(GameState::InProgress));
+        assert!(game.make_uci_move("e1g1") == Ok(GameState::InProgress));
+        assert!(game.get_board()[7][5] == Some(Piece::Rook));
+        assert!(game.get_san_moves() == ["bxa8=N", "Ke7", "O-O"]);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{Game, Move};
+
+impl Game {
+    // Counts the positions reached by playing every sequence of legal moves of the given length
+    // Used to check move generation against known counts, so draws by rule are not taken into account
+    // The game is left as it was
+    pub fn perft(&mut self, depth : u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.generate_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        let mut nodes = 0;
+        for chess_move in moves {
+            let entry = self.apply_move(chess_move);
+            nodes += self.perft(depth - 1);
+            self.revert_move(&entry);
+        }
+        return nodes
+    }
+
+    // Returns the perft count below each legal move, for finding the moves a count is off by
+    // The depth includes the listed moves themselves, so the counts add up to perft(depth)
+    pub fn divide(&mut self, depth : u32) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+        let mut counts = Vec::new();
+        for chess_move in self.generate_moves() {
+            let entry = self.apply_move(chess_move);
+            counts.push((chess_move, self.perft(depth - 1)));
+            self.revert_move(&entry);
+        }
+        return counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::START_FEN;
+
+    // Positions and node counts from the Chess Programming Wiki's perft results page
+    const KIWIPETE : &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3 : &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4 : &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_4_MIRRORED : &str = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+    const POSITION_5 : &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6 : &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    fn assert_perft(fen : &str, counts : &[u64]) {
+        let mut game = Game::from_fen(fen).unwrap();
+        for (depth, expected) in counts.iter().enumerate() {
+            let nodes = game.perft(depth as u32 + 1);
+            assert!(nodes == *expected, "{} at depth {}: expected {}, found {}", fen, depth + 1, expected, nodes);
+        }
+        assert!(game.to_fen() == Game::from_fen(fen).unwrap().to_fen());
+    }
+
+    #[test]
+    fn start_position() {
+        assert_perft(START_FEN, &[20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn kiwipete() {
+        assert_perft(KIWIPETE, &[48, 2039, 97862]);
+    }
+
+    #[test]
+    fn position_3() {
+        assert_perft(POSITION_3, &[14, 191, 2812, 43238]);
+    }
+
+    #[test]
+    fn position_4() {
+        assert_perft(POSITION_4, &[6, 264, 9467]);
+        assert_perft(POSITION_4_MIRRORED, &[6, 264, 9467]);
+    }
+
+    #[test]
+    fn position_5() {
+        assert_perft(POSITION_5, &[44, 1486, 62379]);
+    }
+
+    #[test]
+    fn position_6() {
+        assert_perft(POSITION_6, &[46, 2079, 89890]);
+    }
+
+    #[test]
+    fn divide() {
+        let mut game = Game::from_fen(KIWIPETE).unwrap();
+        let counts = game.divide(2);
+        assert!(counts.len() == 48);
+        assert!(counts.iter().map(|(_, nodes)| nodes).sum::<u64>() == 2039);
+        assert!(counts.contains(&(Move::from_uci("e1g1").unwrap(), 43)));
+        assert!(counts.contains(&(Move::from_uci("d5e6").unwrap(), 46)));
+    }
+}
@@ -0,0 +1,783 @@
+use std::fmt;
+
+use crate::san::{self, SanError};
+use crate::{get_opposite_color, Color, FenError, Game, GameState, START_FEN};
+
+// The outcome of a game as recorded in PGN
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins, // "1-0"
+    BlackWins, // "0-1"
+    Draw, // "1/2-1/2"
+    Unknown // "*", the game is ongoing, abandoned or its result is not known
+}
+
+impl GameResult {
+    // Returns the PGN token for the result
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => {"1-0"},
+            GameResult::BlackWins => {"0-1"},
+            GameResult::Draw => {"1/2-1/2"},
+            GameResult::Unknown => {"*"}
+        }
+    }
+
+    // Parses a PGN result token
+    fn from_token(token : &str) -> Option<GameResult> {
+        match token {
+            "1-0" => {Some(GameResult::WhiteWins)},
+            "0-1" => {Some(GameResult::BlackWins)},
+            "1/2-1/2" => {Some(GameResult::Draw)},
+            "*" => {Some(GameResult::Unknown)},
+            _ => {None}
+        }
+    }
+}
+
+// A single game read from PGN
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>, // Tag pairs in the order they appeared
+    pub comments: Vec<String>, // Comments before the first move
+    pub moves: Vec<PgnMove>, // The mainline
+    pub result: GameResult
+}
+
+// A move in SAN together with its annotations
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgnMove {
+    pub san: String,
+    pub nags: Vec<u8>, // Numeric annotation glyphs, with suffixes like "!?" converted to their NAG
+    pub comments: Vec<String>, // Comments following the move
+    pub variations: Vec<Vec<PgnMove>> // Alternatives to this move, each starting from the position before it
+}
+
+impl PgnGame {
+    // Returns the value of the first tag with the given name, if any
+    pub fn tag(&self, name : &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    // Returns the position the game starts from, taken from the FEN tag if there is one
+    pub fn start_position(&self) -> Result<Game, FenError> {
+        match self.tag("FEN") {
+            Some(fen) => {Game::from_fen(fen)},
+            None => {Ok(Game::new())}
+        }
+    }
+
+    // Replays the mainline and returns the final position
+    pub fn replay(&self) -> Result<Game, PgnReplayError> {
+        let mut game = self.start_position().map_err(PgnReplayError::InvalidFen)?;
+        for pgn_move in &self.moves {
+            san::play(&mut game, &pgn_move.san).map_err(PgnReplayError::InvalidMove)?;
+        }
+        return Ok(game)
+    }
+}
+
+// The Seven Tag Roster every exported game starts with, and the value used when a tag is missing
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*")
+];
+
+// Movetext lines are wrapped to fit within 80 columns including the line break
+const MAX_LINE_LENGTH: usize = 79;
+
+impl PgnGame {
+    // Sets the value of a tag, replacing it if it already exists and adding it at the end otherwise
+    pub fn set_tag(&mut self, name : &str, value : &str) -> () {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old_value)) => {*old_value = value.to_string()},
+            None => {self.tags.push((name.to_string(), value.to_string()))}
+        }
+    }
+
+    // Returns the game in PGN export format
+    // The Seven Tag Roster comes first, filled in with unknown values where tags are missing and with the
+    // Result tag always matching the result, followed by any other tags in their original order
+    pub fn to_pgn(&self) -> String {
+        let mut output = String::new();
+        for (name, default) in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => {self.result.as_str()},
+                _ => {self.tag(name).unwrap_or(default)}
+            };
+            output.push_str(&format_tag(name, value));
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.iter().any(|(roster_name, _)| roster_name == name) {
+                output.push_str(&format_tag(name, value));
+            }
+        }
+        output.push('\n');
+
+        // Numbering continues from the starting position if the game was set up from a FEN
+        let (number, player) = match self.start_position() {
+            Ok(game) => {(game.get_fullmove_number(), game.get_player())},
+            Err(_) => {(1, Color::White)}
+        };
+        let mut tokens = Vec::new();
+        for comment in &self.comments {
+            push_comment(&mut tokens, comment);
+        }
+        push_line(&mut tokens, &self.moves, number, player, !self.comments.is_empty());
+        tokens.push(self.result.as_str().to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+                output.push_str(&line);
+                output.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        output.push_str(&line);
+        output.push_str("\n\n");
+        return output
+    }
+}
+
+// Returns several games in PGN export format, one after the other
+pub fn write_pgn(games : &[PgnGame]) -> String {
+    games.iter().map(|game| game.to_pgn()).collect::<String>()
+}
+
+// Returns a tag pair line, escaping quotes and backslashes in the value
+fn format_tag(name : &str, value : &str) -> String {
+    format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Adds a comment as one token per word, so long comments can be wrapped like the rest of the movetext
+fn push_comment(tokens : &mut Vec<String>, comment : &str) -> () {
+    let comment = comment.replace('}', "");
+    let words = comment.split_whitespace().collect::<Vec<&str>>();
+    if words.is_empty() {
+        tokens.push(String::from("{}"));
+        return
+    }
+    for (index, word) in words.iter().enumerate() {
+        let mut token = word.to_string();
+        if index == 0 {
+            token.insert(0, '{');
+        }
+        if index == words.len() - 1 {
+            token.push('}');
+        }
+        tokens.push(token);
+    }
+}
+
+// Adds the tokens of a line of moves starting with the given move number and player
+// Black's moves get their own number, like "3...", at the start of a line and after comments or variations
+// A move number shares a token with its move, so wrapping never leaves the number at the end of a line
+fn push_line(tokens : &mut Vec<String>, moves : &[PgnMove], mut number : u32, mut player : Color, interrupted : bool) -> () {
+    let mut needs_number = true;
+    let mut interrupted = interrupted;
+    for pgn_move in moves {
+        match player {
+            Color::White => {tokens.push(format!("{}. {}", number, pgn_move.san))},
+            Color::Black if needs_number || interrupted => {tokens.push(format!("{}... {}", number, pgn_move.san))},
+            Color::Black => {tokens.push(pgn_move.san.clone())}
+        }
+        for nag in &pgn_move.nags {
+            tokens.push(format!("${}", nag));
+        }
+        needs_number = false;
+        interrupted = false;
+        for comment in &pgn_move.comments {
+            push_comment(tokens, comment);
+            interrupted = true;
+        }
+        for variation in &pgn_move.variations {
+            let mut variation_tokens = Vec::new();
+            push_line(&mut variation_tokens, variation, number, player, false);
+            if let Some(first) = variation_tokens.first_mut() {
+                first.insert(0, '(');
+                variation_tokens.last_mut().unwrap().push(')');
+                tokens.append(&mut variation_tokens);
+                interrupted = true;
+            }
+        }
+        if player == Color::Black {
+            number += 1;
+        }
+        player = get_opposite_color(player);
+    }
+}
+
+impl Game {
+    // Returns a record of the game so far that can be exported with PgnGame::to_pgn
+    // The Seven Tag Roster is filled in with unknown values, and games not starting from the standard
+    // position get SetUp and FEN tags
+    pub fn to_pgn_game(&self) -> PgnGame {
+        let result = match self.state {
+            // The player to move has been checkmated or has resigned
+            GameState::Checkmate | GameState::GameOver => {
+                match self.player {
+                    Color::White => {GameResult::BlackWins},
+                    Color::Black => {GameResult::WhiteWins}
+                }
+            },
+            GameState::Stalemate | GameState::Draw(_) => {GameResult::Draw},
+            GameState::InProgress | GameState::Check => {GameResult::Unknown}
+        };
+        let mut tags = SEVEN_TAG_ROSTER.iter()
+            .map(|(name, default)| (name.to_string(), default.to_string()))
+            .collect::<Vec<(String, String)>>();
+        tags[6].1 = result.as_str().to_string();
+        if self.start_fen != START_FEN {
+            tags.push((String::from("SetUp"), String::from("1")));
+            tags.push((String::from("FEN"), self.start_fen.clone()));
+        }
+        let moves = self.san_moves.iter()
+            .map(|san| PgnMove { san: san.clone(), nags: Vec::new(), comments: Vec::new(), variations: Vec::new() })
+            .collect();
+        PgnGame { tags, comments: Vec::new(), moves, result }
+    }
+}
+
+// Reasons a PgnGame could not be replayed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PgnReplayError {
+    InvalidFen(FenError),
+    InvalidMove(SanError)
+}
+
+impl fmt::Display for PgnReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnReplayError::InvalidFen(error) => {write!(f, "invalid FEN tag: {}", error)},
+            PgnReplayError::InvalidMove(error) => {write!(f, "{}", error)}
+        }
+    }
+}
+
+impl std::error::Error for PgnReplayError {}
+
+// An error while reading PGN, with the line and column (both starting at 1) where it was found
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: PgnErrorKind
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PgnErrorKind {
+    UnexpectedCharacter(char), // A character that can not start any PGN token
+    UnexpectedToken(String), // A valid token in a place it is not allowed, e.g. a NAG before any move
+    UnterminatedString,
+    UnterminatedComment,
+    UnterminatedVariation,
+    InvalidTag, // A tag pair not of the form [Name "value"]
+    InvalidNag(String),
+    InvalidFen(FenError), // The FEN tag could not be read
+    InvalidMove(SanError) // A move could not be parsed or is not legal in its position
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.kind {
+            PgnErrorKind::UnexpectedCharacter(character) => {write!(f, "unexpected character '{}'", character)},
+            PgnErrorKind::UnexpectedToken(token) => {write!(f, "unexpected \"{}\"", token)},
+            PgnErrorKind::UnterminatedString => {write!(f, "unterminated string")},
+            PgnErrorKind::UnterminatedComment => {write!(f, "unterminated comment")},
+            PgnErrorKind::UnterminatedVariation => {write!(f, "unterminated variation")},
+            PgnErrorKind::InvalidTag => {write!(f, "invalid tag pair")},
+            PgnErrorKind::InvalidNag(nag) => {write!(f, "invalid annotation \"{}\"", nag)},
+            PgnErrorKind::InvalidFen(error) => {write!(f, "invalid FEN tag: {}", error)},
+            PgnErrorKind::InvalidMove(error) => {write!(f, "{}", error)}
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TokenKind {
+    TagStart,
+    TagEnd,
+    String(String),
+    Symbol(String), // Moves and tag names
+    MoveNumber,
+    Nag(u8),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Result(GameResult)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize
+}
+
+// Splits PGN text into tokens, tracking where each one starts
+struct Lexer<'a> {
+    characters: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text : &'a str) -> Lexer<'a> {
+        Lexer { characters: text.chars().peekable(), line: 1, column: 1 }
+    }
+
+    fn error(&self, line : usize, column : usize, kind : PgnErrorKind) -> PgnError {
+        PgnError { line, column, kind }
+    }
+
+    // Consumes and returns the next character, keeping the position up to date
+    fn next_char(&mut self) -> Option<char> {
+        let character = self.characters.next()?;
+        if character == '\n' {
+            self.line += 1;
+            self.column = 1;
+        }
+        else {
+            self.column += 1;
+        }
+        return Some(character)
+    }
+
+    // Consumes characters while they match the predicate and returns them
+    fn take_while(&mut self, predicate : impl Fn(char) -> bool) -> String {
+        let mut taken = String::new();
+        while let Some(&character) = self.characters.peek() {
+            if !predicate(character) {
+                break;
+            }
+            taken.push(character);
+            self.next_char();
+        }
+        return taken
+    }
+
+    fn tokenize(mut self) -> Result<Vec<Token>, PgnError> {
+        let mut tokens = Vec::new();
+        while let Some(&character) = self.characters.peek() {
+            let (line, column) = (self.line, self.column);
+            let kind = match character {
+                _ if character.is_whitespace() => {
+                    self.next_char();
+                    continue;
+                },
+                // Lines starting with '%' are escaped and ignored
+                '%' if column == 1 => {
+                    self.take_while(|character| character != '\n');
+                    continue;
+                },
+                '[' => {
+                    self.next_char();
+                    TokenKind::TagStart
+                },
+                ']' => {
+                    self.next_char();
+                    TokenKind::TagEnd
+                },
+                '(' => {
+                    self.next_char();
+                    TokenKind::VariationStart
+                },
+                ')' => {
+                    self.next_char();
+                    TokenKind::VariationEnd
+                },
+                '*' => {
+                    self.next_char();
+                    TokenKind::Result(GameResult::Unknown)
+                },
+                // Stray periods can only belong to move numbers, e.g. "1. ... e5"
+                '.' => {
+                    self.take_while(|character| character == '.');
+                    continue;
+                },
+                '"' => {
+                    self.next_char();
+                    let mut value = String::new();
+                    loop {
+                        match self.next_char() {
+                            Some('"') => {break},
+                            Some('\\') => {
+                                match self.next_char() {
+                                    Some(escaped) => {value.push(escaped)},
+                                    None => {return Err(self.error(line, column, PgnErrorKind::UnterminatedString))}
+                                }
+                            },
+                            Some('\n') | None => {return Err(self.error(line, column, PgnErrorKind::UnterminatedString))},
+                            Some(character) => {value.push(character)}
+                        }
+                    }
+                    TokenKind::String(value)
+                },
+                '{' => {
+                    self.next_char();
+                    let comment = self.take_while(|character| character != '}');
+                    if self.next_char().is_none() {
+                        return Err(self.error(line, column, PgnErrorKind::UnterminatedComment));
+                    }
+                    TokenKind::Comment(comment.trim().to_string())
+                },
+                ';' => {
+                    self.next_char();
+                    let comment = self.take_while(|character| character != '\n');
+                    TokenKind::Comment(comment.trim().to_string())
+                },
+                '$' => {
+                    self.next_char();
+                    let digits = self.take_while(|character| character.is_ascii_digit());
+                    match digits.parse::<u8>() {
+                        Ok(nag) => {TokenKind::Nag(nag)},
+                        Err(_) => {return Err(self.error(line, column, PgnErrorKind::InvalidNag(format!("${}", digits))))}
+                    }
+                },
+                '!' | '?' => {
+                    let suffix = self.take_while(|character| character == '!' || character == '?');
+                    let nag = match suffix.as_str() {
+                        "!" => {1},
+                        "?" => {2},
+                        "!!" => {3},
+                        "??" => {4},
+                        "!?" => {5},
+                        "?!" => {6},
+                        _ => {return Err(self.error(line, column, PgnErrorKind::InvalidNag(suffix)))}
+                    };
+                    TokenKind::Nag(nag)
+                },
+                _ if character.is_ascii_alphanumeric() => {
+                    let symbol = self.take_while(|character| character.is_ascii_alphanumeric() || "_+#=:-/".contains(character));
+                    if symbol.chars().all(|character| character.is_ascii_digit()) {
+                        self.take_while(|character| character == '.');
+                        TokenKind::MoveNumber
+                    }
+                    else {
+                        match GameResult::from_token(&symbol) {
+                            Some(result) => {TokenKind::Result(result)},
+                            None => {TokenKind::Symbol(symbol)}
+                        }
+                    }
+                },
+                _ => {return Err(self.error(line, column, PgnErrorKind::UnexpectedCharacter(character)))}
+            };
+            tokens.push(Token { kind, line, column });
+        }
+        return Ok(tokens)
+    }
+}
+
+// Turns tokens into games, replaying every move to check that it is legal
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    end: (usize, usize) // Line and column just past the end of the text, used for errors at the end
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        return token
+    }
+
+    fn error_at(token : &Token, kind : PgnErrorKind) -> PgnError {
+        PgnError { line: token.line, column: token.column, kind }
+    }
+
+    fn error_at_end(&self, kind : PgnErrorKind) -> PgnError {
+        PgnError { line: self.end.0, column: self.end.1, kind }
+    }
+
+    fn parse_game(&mut self) -> Result<PgnGame, PgnError> {
+        let mut tags = Vec::new();
+        let mut fen_token = None;
+        while self.peek().map(|token| &token.kind) == Some(&TokenKind::TagStart) {
+            let start = self.next().unwrap();
+            let name = self.next();
+            let value = self.next();
+            let end = self.next();
+            match (name.map(|token| token.kind), value.map(|token| token.kind), end.map(|token| token.kind)) {
+                (Some(TokenKind::Symbol(name)), Some(TokenKind::String(value)), Some(TokenKind::TagEnd)) => {
+                    if name == "FEN" {
+                        fen_token = Some(start.clone());
+                    }
+                    tags.push((name, value));
+                },
+                _ => {return Err(Parser::error_at(&start, PgnErrorKind::InvalidTag))}
+            }
+        }
+
+        let mut pgn_game = PgnGame { tags, comments: Vec::new(), moves: Vec::new(), result: GameResult::Unknown };
+        let game = match fen_token {
+            Some(token) => {
+                pgn_game.start_position().map_err(|error| Parser::error_at(&token, PgnErrorKind::InvalidFen(error)))?
+            },
+            None => {Game::new()}
+        };
+        let (moves, result) = self.parse_line(game, &mut pgn_game.comments, false)?;
+        pgn_game.moves = moves;
+        // Without a result token the Result tag is the best information there is
+        pgn_game.result = result
+            .or_else(|| pgn_game.tag("Result").and_then(GameResult::from_token))
+            .unwrap_or(GameResult::Unknown);
+        return Ok(pgn_game)
+    }
+
+    // Parses moves starting from the given position until the end of the line
+    // The mainline ends at a result token, the next game's tags or the end of the text
+    // A variation ends at its closing parenthesis
+    fn parse_line(&mut self, mut game : Game, comments : &mut Vec<String>, is_variation : bool)
+        -> Result<(Vec<PgnMove>, Option<GameResult>), PgnError> {
+        let mut moves : Vec<PgnMove> = Vec::new();
+        loop {
+            let token = match self.peek() {
+                Some(token) => {token.clone()},
+                None if is_variation => {return Err(self.error_at_end(PgnErrorKind::UnterminatedVariation))},
+                None => {return Ok((moves, None))}
+            };
+            match token.kind {
+                TokenKind::TagStart if !is_variation => {return Ok((moves, None))},
+                TokenKind::Result(result) if !is_variation => {
+                    self.next();
+                    return Ok((moves, Some(result)));
+                },
+                TokenKind::VariationEnd if is_variation => {
+                    self.next();
+                    return Ok((moves, None));
+                },
+                TokenKind::MoveNumber => {
+                    self.next();
+                },
+                TokenKind::Symbol(ref san) => {
+                    self.next();
+                    san::play(&mut game, san).map_err(|error| Parser::error_at(&token, PgnErrorKind::InvalidMove(error)))?;
+                    moves.push(PgnMove { san: san.clone(), nags: Vec::new(), comments: Vec::new(), variations: Vec::new() });
+                },
+                TokenKind::Nag(nag) => {
+                    self.next();
+                    match moves.last_mut() {
+                        Some(last) => {last.nags.push(nag)},
+                        None => {return Err(Parser::error_at(&token, PgnErrorKind::UnexpectedToken(format!("${}", nag))))}
+                    }
+                },
+                TokenKind::Comment(ref comment) => {
+                    self.next();
+                    match moves.last_mut() {
+                        Some(last) => {last.comments.push(comment.clone())},
+                        None => {comments.push(comment.clone())}
+                    }
+                },
+                TokenKind::VariationStart if !moves.is_empty() => {
+                    self.next();
+                    // A variation replaces the last move, so only it gets a copy of the position from before that move
+                    game.undo();
+                    let start = game.clone();
+                    game.redo();
+                    let mut leading_comments = Vec::new();
+                    let (mut variation, _) = self.parse_line(start, &mut leading_comments, true)?;
+                    // Comments before the first move of a variation are kept with that move
+                    if let Some(first) = variation.first_mut() {
+                        leading_comments.append(&mut first.comments);
+                        first.comments = leading_comments;
+                    }
+                    moves.last_mut().unwrap().variations.push(variation);
+                },
+                _ => {return Err(Parser::error_at(&token, PgnErrorKind::UnexpectedToken(token_text(&token.kind))))}
+            }
+        }
+    }
+}
+
+// Returns the PGN text of a token, for use in error messages
+fn token_text(kind : &TokenKind) -> String {
+    match kind {
+        TokenKind::TagStart => {String::from("[")},
+        TokenKind::TagEnd => {String::from("]")},
+        TokenKind::String(value) => {format!("\"{}\"", value)},
+        TokenKind::Symbol(symbol) => {symbol.clone()},
+        TokenKind::MoveNumber => {String::from("move number")},
+        TokenKind::Nag(nag) => {format!("${}", nag)},
+        TokenKind::Comment(comment) => {format!("{{{}}}", comment)},
+        TokenKind::VariationStart => {String::from("(")},
+        TokenKind::VariationEnd => {String::from(")")},
+        TokenKind::Result(result) => {result.as_str().to_string()}
+    }
+}
+
+// Reads every game in a PGN text
+// All moves, including those in variations, are checked against the rules and the first error stops reading
+pub fn parse_pgn(text : &str) -> Result<Vec<PgnGame>, PgnError> {
+    let tokens = Lexer::new(text).tokenize()?;
+    let last_line = text.split('\n').count();
+    let last_column = text.split('\n').next_back().unwrap().chars().count() + 1;
+    let mut parser = Parser { tokens, position: 0, end: (last_line, last_column) };
+    let mut games = Vec::new();
+    while parser.peek().is_some() {
+        games.push(parser.parse_game()?);
+    }
+    return Ok(games)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, GameState, Piece};
+
+    const IMMORTAL_GAME: &str = r#"[Event "London"]
+[Site "London ENG"]
+[Date "1851.06.21"]
+[Round "?"]
+[White "Adolf Anderssen"]
+[Black "Lionel Adalbert Bagration Felix Kieseritzky"]
+[Result "1-0"]
+
+1.e4 e5 2.f4 exf4 3.Bc4 Qh4+ 4.Kf1 b5 5.Bxb5 Nf6 6.Nf3 Qh6 7.d3 Nh5 8.Nh4 Qg5
+9.Nf5 c6 10.g4 Nf6 11.Rg1 cxb5 12.h4 Qg6 13.h5 Qg5 14.Qf3 Ng8 15.Bxf4 Qf6
+16.Nc3 Bc5 17.Nd5 Qxb2 18.Bd6 Bxg1 19.e5 Qxa1+ 20.Ke2 Na6 21.Nxg7+ Kd8
+22.Qf6+ Nxf6 23.Be7# 1-0
+"#;
+
+    #[test]
+    fn immortal_game() {
+        let games = parse_pgn(IMMORTAL_GAME).unwrap();
+        assert!(games.len() == 1);
+        let game = &games[0];
+        assert!(game.tag("White") == Some("Adolf Anderssen"));
+        assert!(game.tags.len() == 7);
+        assert!(game.moves.len() == 45);
+        assert!(game.moves[44].san == "Be7#");
+        assert!(game.result == GameResult::WhiteWins);
+        let final_position = game.replay().unwrap();
+        assert!(final_position.get_game_state() == GameState::Checkmate);
+    }
+
+    #[test]
+    fn annotations() {
+        let text = r#"[Event "Annotated \"test\""]
+
+{Opening comment} 1. e4 $1 e5!? ; rest of line
+2. Nf3 (2. f4 {King's gambit} exf4 (2... d5) 3. Nf3) (2. Bc4) 2... Nc6 {Developing}
+3. Bb5 a6 *"#;
+        let games = parse_pgn(text).unwrap();
+        let game = &games[0];
+        assert!(game.tag("Event") == Some("Annotated \"test\""));
+        assert!(game.comments == vec![String::from("Opening comment")]);
+        assert!(game.moves[0].nags == vec![1]);
+        assert!(game.moves[1].nags == vec![5]);
+        assert!(game.moves[1].comments == vec![String::from("rest of line")]);
+        assert!(game.moves[2].variations.len() == 2);
+        let gambit = &game.moves[2].variations[0];
+        assert!(gambit.iter().map(|pgn_move| pgn_move.san.as_str()).collect::<Vec<&str>>() == vec!["f4", "exf4", "Nf3"]);
+        assert!(gambit[0].comments == vec![String::from("King's gambit")]);
+        assert!(gambit[1].variations[0][0].san == "d5");
+        assert!(game.moves[2].variations[1][0].san == "Bc4");
+        assert!(game.moves[3].comments == vec![String::from("Developing")]);
+        assert!(game.moves.len() == 6);
+        assert!(game.result == GameResult::Unknown);
+    }
+
+    #[test]
+    fn multiple_games() {
+        let text = "[Event \"First\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n\n[Event \"Second\"]\n[FEN \"4k3/P7/8/8/8/8/8/4K3 w - - 0 1\"]\n[SetUp \"1\"]\n\n1. a8=N 1/2-1/2\n";
+        let games = parse_pgn(text).unwrap();
+        assert!(games.len() == 2);
+        assert!(games[0].result == GameResult::BlackWins);
+        assert!(games[0].replay().unwrap().get_game_state() == GameState::Checkmate);
+        assert!(games[1].result == GameResult::Draw);
+        assert!(games[1].replay().unwrap().get_board()[0][0] == Some(Piece::Knight));
+    }
+
+    #[test]
+    fn errors() {
+        let error = parse_pgn("[Event \"Test\"]\n\n1. e4 e5 2. Ke3 *").unwrap_err();
+        assert!(error == PgnError { line: 3, column: 13, kind: PgnErrorKind::InvalidMove(SanError::IllegalMove(String::from("Ke3"))) });
+        let error = parse_pgn("1. e4 e5\n2. Nf3 Nc6 3. Zz4").unwrap_err();
+        assert!(error == PgnError { line: 2, column: 15, kind: PgnErrorKind::InvalidMove(SanError::InvalidSyntax(String::from("Zz4"))) });
+        let error = parse_pgn("1. e4 (1. d4 d5 2. Qd3").unwrap_err();
+        assert!(error == PgnError { line: 1, column: 23, kind: PgnErrorKind::UnterminatedVariation });
+        let error = parse_pgn("1. e4 {unfinished").unwrap_err();
+        assert!(error == PgnError { line: 1, column: 7, kind: PgnErrorKind::UnterminatedComment });
+        let error = parse_pgn("[Event Test]").unwrap_err();
+        assert!(error == PgnError { line: 1, column: 1, kind: PgnErrorKind::InvalidTag });
+        let error = parse_pgn("[Event \"Test]").unwrap_err();
+        assert!(error == PgnError { line: 1, column: 8, kind: PgnErrorKind::UnterminatedString });
+        let error = parse_pgn("$3 1. e4").unwrap_err();
+        assert!(error == PgnError { line: 1, column: 1, kind: PgnErrorKind::UnexpectedToken(String::from("$3")) });
+        let error = parse_pgn("1. e4 e5 )").unwrap_err();
+        assert!(error == PgnError { line: 1, column: 10, kind: PgnErrorKind::UnexpectedToken(String::from(")")) });
+        let error = parse_pgn("1. e4 e5 <").unwrap_err();
+        assert!(error == PgnError { line: 1, column: 10, kind: PgnErrorKind::UnexpectedCharacter('<') });
+        let error = parse_pgn("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n1. e4").unwrap_err();
+        assert!(error == PgnError { line: 1, column: 1, kind: PgnErrorKind::InvalidFen(FenError::WrongKingCount(Color::White)) });
+        let error = parse_pgn("1. Nf3 Nf6 2. Nc3 Nc6 3. Nb5 Nb4 4. Nd4").unwrap_err();
+        assert!(error == PgnError { line: 1, column: 37, kind: PgnErrorKind::InvalidMove(SanError::AmbiguousMove(String::from("Nd4"))) });
+    }
+
+    #[test]
+    fn export_played_game() {
+        let mut game = Game::new();
+        for (from, to) in [("E2", "E4"), ("E7", "E5"), ("D1", "H5"), ("B8", "C6"), ("F1", "C4"), ("G8", "F6"), ("H5", "F7")] {
+            assert!(game.make_move(from, to).is_ok());
+        }
+        let mut record = game.to_pgn_game();
+        record.set_tag("White", "Scholar");
+        record.set_tag("Annotator", "Test \"quoted\"");
+        let expected = "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n[White \"Scholar\"]\n[Black \"?\"]\n[Result \"1-0\"]\n[Annotator \"Test \\\"quoted\\\"\"]\n\n1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0\n\n";
+        assert!(record.to_pgn() == expected);
+        let read_back = parse_pgn(&record.to_pgn()).unwrap();
+        assert!(read_back == vec![record]);
+    }
+
+    #[test]
+    fn export_from_position() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/7p/8/R3K3 b Q - 0 40").unwrap();
+        assert!(san::play(&mut game, "Kd7").is_ok());
+        assert!(san::play(&mut game, "O-O-O+").is_ok());
+        let record = game.to_pgn_game();
+        assert!(record.tag("FEN") == Some("4k3/8/8/8/8/7p/8/R3K3 b Q - 0 40"));
+        assert!(record.to_pgn().ends_with("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/7p/8/R3K3 b Q - 0 40\"]\n\n40... Kd7 41. O-O-O+ *\n\n"));
+    }
+
+    #[test]
+    fn export_annotations() {
+        let text = "{Start} 1. e4 $1 {Best by test} e5 (1... c5 2. Nf3 (2. c3) 2... d6) 2. Nf3 *";
+        let games = parse_pgn(text).unwrap();
+        let pgn = games[0].to_pgn();
+        assert!(pgn.ends_with("\n\n{Start} 1. e4 $1 {Best by test} 1... e5 (1... c5 2. Nf3 (2. c3) 2... d6) 2. Nf3\n*\n\n"));
+        let read_back = parse_pgn(&pgn).unwrap();
+        assert!(read_back[0].moves == games[0].moves);
+        assert!(read_back[0].comments == games[0].comments);
+    }
+
+    #[test]
+    fn export_wrapping() {
+        let games = parse_pgn(IMMORTAL_GAME).unwrap();
+        let pgn = write_pgn(&games);
+        assert!(pgn.lines().all(|line| line.len() < 80));
+        // A move number is never split from its move
+        assert!(pgn.lines().all(|line| !line.ends_with('.')));
+        assert!(pgn.contains("\n\n1. e4 e5 2. f4 exf4 3. Bc4 Qh4+ 4. Kf1 b5 5. Bxb5 Nf6 6. Nf3 Qh6 7. d3 Nh5\n8. Nh4 Qg5 9. Nf5"));
+        assert!(parse_pgn(&pgn).unwrap() == games);
+        let record = games[0].replay().unwrap().to_pgn_game();
+        assert!(record.moves == games[0].moves);
+    }
+}
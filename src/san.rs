@@ -0,0 +1,372 @@
+use std::fmt;
+
+use crate::{Game, GameState, Move, MoveError, Piece, Square};
+
+// Reasons a move in Standard Algebraic Notation could not be played
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SanError {
+    InvalidSyntax(String), // The text is not a SAN move at all
+    IllegalMove(String), // No legal move in the position matches the text
+    AmbiguousMove(String), // More than one legal move matches the text
+    InvalidMove(String, MoveError) // The matching move could not be played, for the reason in the MoveError
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SanError::InvalidSyntax(san) => {write!(f, "\"{}\" is not a valid SAN move", san)},
+            SanError::IllegalMove(san) => {write!(f, "\"{}\" is not a legal move", san)},
+            SanError::AmbiguousMove(san) => {write!(f, "\"{}\" matches more than one legal move", san)},
+            SanError::InvalidMove(san, error) => {write!(f, "\"{}\" can not be played: {}", san, error)}
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+// A SAN move split into its parts, not yet checked against any position
+// Coordinates are numerical, with y = 0 being the eighth rank
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SanParts {
+    Castle { king_side: bool },
+    Normal {
+        piece: Piece,
+        from_x: Option<usize>, // File given to disambiguate, or the capturing pawn's file
+        from_y: Option<usize>, // Rank given to disambiguate
+        to: (usize, usize),
+        capture: bool,
+        promotion: Option<Piece>
+    }
+}
+
+// Splits a SAN move into its parts
+// A single trailing check or checkmate marker is accepted and ignored
+fn parse_parts(san : &str) -> Result<SanParts, SanError> {
+    let invalid = || SanError::InvalidSyntax(san.to_string());
+    let body = san.strip_suffix('+').or_else(|| san.strip_suffix('#')).unwrap_or(san);
+    match body {
+        "O-O" | "0-0" => {return Ok(SanParts::Castle { king_side: true })},
+        "O-O-O" | "0-0-0" => {return Ok(SanParts::Castle { king_side: false })},
+        _ => {}
+    }
+    let mut characters = body.chars().collect::<Vec<char>>();
+
+    let piece = match characters.first().and_then(|character| letter_to_piece(*character)) {
+        Some(piece) => {
+            characters.remove(0);
+            piece
+        },
+        None => {Piece::Pawn}
+    };
+
+    // Promotion is written as "e8=Q", but "e8Q" is common enough to accept as well
+    let mut promotion = None;
+    if let Some(promoted) = characters.last().and_then(|character| letter_to_piece(*character)) {
+        if promoted == Piece::King || piece != Piece::Pawn {
+            return Err(invalid());
+        }
+        promotion = Some(promoted);
+        characters.pop();
+        if characters.last() == Some(&'=') {
+            characters.pop();
+        }
+    }
+
+    // The destination square is always the last two characters
+    if characters.len() < 2 {
+        return Err(invalid());
+    }
+    let rank = characters.pop().unwrap();
+    let (to_x, to_y) = parse_square(characters.pop().unwrap(), rank).ok_or_else(invalid)?.coordinates();
+    let capture = characters.last() == Some(&'x');
+    if capture {
+        characters.pop();
+    }
+
+    // Whatever is left disambiguates the piece by file, rank or both
+    let mut from_x = None;
+    let mut from_y = None;
+    match characters.as_slice() {
+        [] => {},
+        [file, rank] => {
+            let (x, y) = parse_square(*file, *rank).ok_or_else(invalid)?.coordinates();
+            from_x = Some(x);
+            from_y = Some(y);
+        },
+        // A single character is either a file or a rank, completed to a square to read it
+        [character] => {
+            match (parse_square(*character, '8'), parse_square('a', *character)) {
+                (Some(square), _) => {from_x = Some(square.x())},
+                (_, Some(square)) => {from_y = Some(square.y())},
+                _ => {return Err(invalid())}
+            }
+        },
+        _ => {return Err(invalid())}
+    }
+
+    // Pawns name their file when capturing and nothing otherwise
+    if piece == Piece::Pawn && (from_y.is_some() || capture != from_x.is_some()) {
+        return Err(invalid());
+    }
+    if piece == Piece::Pawn && from_x.is_none() {
+        from_x = Some(to_x);
+    }
+    return Ok(SanParts::Normal { piece, from_x, from_y, to: (to_x, to_y), capture, promotion })
+}
+
+// Finds the single legal move described by a SAN string in the game's current position
+pub(crate) fn resolve(game : &Game, san : &str) -> Result<Move, SanError> {
+    let parts = parse_parts(san)?;
+    let illegal = || SanError::IllegalMove(san.to_string());
+    if game.is_finished() {
+        return Err(illegal());
+    }
+    let player = game.get_player();
+    let (piece, from_x, from_y, to, capture, promotion) = match parts {
+        SanParts::Castle { king_side } => {
+            let (king_x, king_y) = game.find_king(player).ok_or_else(illegal)?.coordinates();
+            let to_x = if king_side {6} else {2};
+            (Piece::King, Some(king_x), Some(king_y), (to_x, king_y), false, None)
+        },
+        SanParts::Normal { piece, from_x, from_y, to, capture, promotion } => {
+            (piece, from_x, from_y, to, capture, promotion)
+        }
+    };
+    // Castling is told apart from a king moving normally by its flag
+    let is_castle = matches!(parts, SanParts::Castle { .. });
+
+    // Promotions of the same pawn to different pieces count as one candidate
+    let mut candidates : Vec<Move> = Vec::new();
+    for legal_move in game.legal_moves() {
+        let (x, y) = legal_move.from;
+        if legal_move.to != to || game.board[y][x] != Some(piece) || legal_move.flags.castle != is_castle
+            || from_x.is_some_and(|from_x| from_x != x) || from_y.is_some_and(|from_y| from_y != y)
+            || candidates.iter().any(|candidate| candidate.from == legal_move.from) {
+            continue;
+        }
+        candidates.push(legal_move);
+    }
+    let mut chess_move = match candidates.as_slice() {
+        [chess_move] => {*chess_move},
+        [] => {return Err(illegal())},
+        _ => {return Err(SanError::AmbiguousMove(san.to_string()))}
+    };
+
+    // Captures have to be marked as such, but a missing 'x' is common enough to accept
+    if capture && !chess_move.flags.capture {
+        return Err(illegal());
+    }
+
+    // A pawn reaching the last rank has to say what it promotes to, and no other move may
+    if chess_move.promotion.is_some() != promotion.is_some() {
+        return Err(illegal());
+    }
+    chess_move.promotion = promotion;
+    return Ok(chess_move)
+}
+
+// Plays a move given in SAN and returns the resulting game state
+pub(crate) fn play(game : &mut Game, san : &str) -> Result<GameState, SanError> {
+    let chess_move = resolve(game, san)?;
+    return game.play(chess_move).map(|outcome| outcome.state).map_err(|error| SanError::InvalidMove(san.to_string(), error))
+}
+
+impl Game {
+    // Takes a move in Standard Algebraic Notation, such as "Nbd7", "exd6", "e8=Q+" or "O-O"
+    // If it describes exactly one legal move, returns that move with its flags filled in
+    pub fn parse_san(&self, san : &str) -> Result<Move, SanError> {
+        resolve(self, san)
+    }
+
+    // Makes a move given in Standard Algebraic Notation and returns the resulting game state
+    pub fn make_san_move(&mut self, san : &str) -> Result<GameState, SanError> {
+        play(self, san)
+    }
+
+    // Returns a move in Standard Algebraic Notation, including the check or checkmate suffix
+    // Illegal moves, including promotions without a piece, return None
+    pub fn to_san(&self, chess_move : Move) -> Option<String> {
+        let played = self.legal_move(chess_move).ok()?;
+        let mut san = move_to_san(self, played);
+        // Only the position after the move is needed to tell check from checkmate
+        let mut after_move = self.position_copy();
+        after_move.apply_move(played);
+        if after_move.is_in_check(after_move.player) {
+            san.push(if after_move.has_no_moves(after_move.player) {'#'} else {'+'});
+        }
+        return Some(san)
+    }
+}
+
+// Returns the SAN of a legal move in the game's current position, without the check or checkmate suffix
+pub(crate) fn move_to_san(game : &Game, chess_move : Move) -> String {
+    let (from, to) = (chess_move.from, chess_move.to);
+    let piece = game.board[from.1][from.0].unwrap();
+    let player = game.get_color_at(from.0, from.1).unwrap();
+    if piece == Piece::King && from.0.abs_diff(to.0) == 2 {
+        return String::from(if to.0 == 6 {"O-O"} else {"O-O-O"});
+    }
+    let destination = Square::at(to.0, to.1).to_string().to_ascii_lowercase();
+    let is_capture = game.board[to.1][to.0].is_some() || (piece == Piece::Pawn && from.0 != to.0);
+    let mut san = String::new();
+
+    let origin = Square::at(from.0, from.1);
+    let (file, rank) = (origin.file().to_ascii_lowercase(), char::from(b'0' + origin.rank()));
+    if piece == Piece::Pawn {
+        if is_capture {
+            san.push(file);
+        }
+    }
+    else {
+        san.push(piece_to_letter(piece));
+        // Other pieces of the same kind that could also move to the destination
+        let mut rivals = Vec::new();
+        for y in 0..8 {
+            for x in 0..8 {
+                if (x, y) != from && game.board[y][x] == Some(piece) && game.get_color_at(x, y) == Some(player)
+                    && game.get_legal_destinations(x, y).contains(&to) {
+                    rivals.push((x, y));
+                }
+            }
+        }
+        // Prefer the file, then the rank, and use the whole square only if neither is enough
+        if !rivals.is_empty() {
+            if rivals.iter().all(|(x, _)| *x != from.0) {
+                san.push(file);
+            }
+            else if rivals.iter().all(|(_, y)| *y != from.1) {
+                san.push(rank);
+            }
+            else {
+                san.push(file);
+                san.push(rank);
+            }
+        }
+    }
+    if is_capture {
+        san.push('x');
+    }
+    san.push_str(&destination);
+    if let Some(promotion) = chess_move.promotion {
+        san.push('=');
+        san.push(piece_to_letter(promotion));
+    }
+    return san
+}
+
+// Returns the letter of a piece, which SAN only writes for pieces other than a pawn
+pub(crate) fn piece_to_letter(piece : Piece) -> char {
+    match piece {
+        Piece::King => {'K'},
+        Piece::Queen => {'Q'},
+        Piece::Rook => {'R'},
+        Piece::Bishop => {'B'},
+        Piece::Knight => {'N'},
+        Piece::Pawn => {'P'}
+    }
+}
+
+// Returns the piece a SAN piece letter stands for, pawns have no letter
+pub(crate) fn letter_to_piece(letter : char) -> Option<Piece> {
+    match letter {
+        'K' => {Some(Piece::King)},
+        'Q' => {Some(Piece::Queen)},
+        'R' => {Some(Piece::Rook)},
+        'B' => {Some(Piece::Bishop)},
+        'N' => {Some(Piece::Knight)},
+        _ => {None}
+    }
+}
+
+// Reads a square from its file letter and rank digit, which in SAN has to be a lowercase letter
+fn parse_square(file : char, rank : char) -> Option<Square> {
+    if !file.is_ascii_lowercase() {
+        return None;
+    }
+    return format!("{}{}", file, rank).parse::<Square>().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Color;
+
+    #[test]
+    fn syntax() {
+        assert!(parse_parts("e4") == Ok(SanParts::Normal { piece: Piece::Pawn, from_x: Some(4), from_y: None, to: (4, 4), capture: false, promotion: None }));
+        assert!(parse_parts("exd5") == Ok(SanParts::Normal { piece: Piece::Pawn, from_x: Some(4), from_y: None, to: (3, 3), capture: true, promotion: None }));
+        assert!(parse_parts("Nbd7") == Ok(SanParts::Normal { piece: Piece::Knight, from_x: Some(1), from_y: None, to: (3, 1), capture: false, promotion: None }));
+        assert!(parse_parts("R1a3+") == Ok(SanParts::Normal { piece: Piece::Rook, from_x: None, from_y: Some(7), to: (0, 5), capture: false, promotion: None }));
+        assert!(parse_parts("Qh4xe1#") == Ok(SanParts::Normal { piece: Piece::Queen, from_x: Some(7), from_y: Some(4), to: (4, 7), capture: true, promotion: None }));
+        assert!(parse_parts("e8=Q") == Ok(SanParts::Normal { piece: Piece::Pawn, from_x: Some(4), from_y: None, to: (4, 0), capture: false, promotion: Some(Piece::Queen) }));
+        assert!(parse_parts("dxc1N+") == Ok(SanParts::Normal { piece: Piece::Pawn, from_x: Some(3), from_y: None, to: (2, 7), capture: true, promotion: Some(Piece::Knight) }));
+        assert!(parse_parts("O-O") == Ok(SanParts::Castle { king_side: true }));
+        assert!(parse_parts("O-O-O+") == Ok(SanParts::Castle { king_side: false }));
+        for invalid in ["", "e", "e9", "i4", "Pe4", "xe4", "ed5", "e2e4x", "Nbd7d", "e8=K", "Nf3=Q", "e4++", "o-o", "E4", "NBd7"] {
+            assert!(parse_parts(invalid) == Err(SanError::InvalidSyntax(invalid.to_string())), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn resolution() {
+        let mut game = Game::from_fen("r3k2r/1P6/8/8/8/2N3N1/8/R3K2R w KQkq - 0 1").unwrap();
+        assert!(resolve(&game, "Nce4") == Ok(Move::new((2, 5), (4, 4), None)));
+        assert!(resolve(&game, "Ne4") == Err(SanError::AmbiguousMove(String::from("Ne4"))));
+        assert!(resolve(&game, "Nd4") == Err(SanError::IllegalMove(String::from("Nd4"))));
+        assert!(resolve(&game, "O-O").is_ok_and(|chess_move| chess_move == Move::new((4, 7), (6, 7), None) && chess_move.flags.castle));
+        assert!(resolve(&game, "O-O-O") == Ok(Move::new((4, 7), (2, 7), None)));
+        assert!(resolve(&game, "bxa8=N").is_ok_and(|chess_move| chess_move == Move::new((1, 1), (0, 0), Some(Piece::Knight)) && chess_move.flags.capture));
+        assert!(resolve(&game, "b8") == Err(SanError::IllegalMove(String::from("b8"))));
+        assert!(resolve(&game, "Ncxe4") == Err(SanError::IllegalMove(String::from("Ncxe4"))));
+        assert!(play(&mut game, "b8=R+") == Ok(GameState::Check));
+        assert!(game.get_board()[0][1] == Some(Piece::Rook));
+    }
+
+    #[test]
+    fn generation() {
+        let game = Game::from_fen("r3k2r/1P6/8/3p4/8/2N3N1/8/R3K2R w KQkq - 0 1").unwrap();
+        assert!(move_to_san(&game, Move::new((2, 5), (4, 4), None)) == "Nce4");
+        assert!(move_to_san(&game, Move::new((2, 5), (3, 3), None)) == "Nxd5");
+        assert!(move_to_san(&game, Move::new((4, 7), (6, 7), None)) == "O-O");
+        assert!(move_to_san(&game, Move::new((4, 7), (2, 7), None)) == "O-O-O");
+        assert!(move_to_san(&game, Move::new((0, 7), (3, 7), None)) == "Rd1");
+        assert!(move_to_san(&game, Move::new((1, 1), (0, 0), Some(Piece::Queen))) == "bxa8=Q");
+        assert!(move_to_san(&game, Move::new((1, 1), (1, 0), Some(Piece::Knight))) == "b8=N");
+
+        // Knights on the same file are told apart by rank, and by square when neither is enough
+        let game = Game::from_fen("4k3/8/8/1N3N2/8/1N6/8/4K3 w - - 0 1").unwrap();
+        assert!(move_to_san(&game, Move::new((1, 5), (3, 4), None)) == "N3d4");
+        assert!(move_to_san(&game, Move::new((5, 3), (3, 4), None)) == "Nfd4");
+        assert!(move_to_san(&game, Move::new((1, 3), (3, 4), None)) == "Nb5d4");
+
+        let mut game = Game::new();
+        assert!(game.make_move("F2", "F3").is_ok());
+        assert!(game.make_move("E7", "E5").is_ok());
+        assert!(game.make_move("G2", "G4").is_ok());
+        assert!(game.make_move("D8", "H4").is_ok());
+        assert!(game.get_san_moves() == ["f3", "e5", "g4", "Qh4#"]);
+    }
+
+    #[test]
+    fn public_api() {
+        let mut game = Game::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4").unwrap();
+        assert!(game.parse_san("Qxf7#") == Ok(Move::new((7, 3), (5, 1), None)));
+        assert!(game.parse_san("Nbd7") == Err(SanError::IllegalMove(String::from("Nbd7"))));
+        assert!(game.parse_san("Qf7") == Ok(Move::new((7, 3), (5, 1), None)));
+        assert!(game.to_san(Move::new((7, 3), (5, 1), None)) == Some(String::from("Qxf7#")));
+        assert!(game.to_san(Move::new((2, 4), (5, 1), None)) == Some(String::from("Bxf7+")));
+        assert!(game.to_san(Move::new((4, 7), (4, 5), None)).is_none());
+        // Asking for the SAN does not play the move
+        assert!(game.get_player() == Color::White);
+        assert!(game.make_san_move("Qxf7#") == Ok(GameState::Checkmate));
+        assert!(game.make_san_move("Ke7") == Err(SanError::IllegalMove(String::from("Ke7"))));
+
+        let mut game = Game::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(game.parse_san("b8=N") == Ok(Move::new((1, 1), (1, 0), Some(Piece::Knight))));
+        assert!(game.to_san(Move::new((1, 1), (1, 0), None)).is_none());
+        assert!(game.to_san(Move::new((1, 1), (1, 0), Some(Piece::Queen))) == Some(String::from("b8=Q+")));
+        assert!(game.to_san(Move::new((1, 1), (1, 0), Some(Piece::Knight))) == Some(String::from("b8=N")));
+        assert!(game.make_san_move("b8=R+") == Ok(GameState::Check));
+        assert!(game.get_board()[0][1] == Some(Piece::Rook));
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+use std::str::FromStr;
+
+// A square on the board, written in standard chess coordinates like "E2"
+// Numerically it uses the same x, y coordinates as the rest of the crate, with (0, 0) being A8
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Square(u8); // 8 * y + x
+
+// Reasons a square could not be read or constructed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseSquareError {
+    InvalidLength(usize), // A square is a file letter followed by a rank digit, so exactly 2 characters
+    InvalidFile(char), // The file was not a letter a-h, in either case
+    InvalidRank(char), // The rank was not a digit 1-8
+    OutOfBounds(usize, usize) // The numerical x, y coordinates are not on the board
+}
+
+impl fmt::Display for ParseSquareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseSquareError::InvalidLength(length) => {write!(f, "a square can not be {} characters long", length)},
+            ParseSquareError::InvalidFile(file) => {write!(f, "invalid file '{}'", file)},
+            ParseSquareError::InvalidRank(rank) => {write!(f, "invalid rank '{}'", rank)},
+            ParseSquareError::OutOfBounds(x, y) => {write!(f, "({}, {}) is not on the board", x, y)}
+        }
+    }
+}
+
+impl std::error::Error for ParseSquareError {}
+
+impl Square {
+    // Constructs a square from numerical coordinates
+    pub fn new(x : usize, y : usize) -> Result<Square, ParseSquareError> {
+        if x > 7 || y > 7 {
+            return Err(ParseSquareError::OutOfBounds(x, y));
+        }
+        return Ok(Square::at(x, y))
+    }
+
+    // Constructs a square from coordinates the crate already knows are on the board
+    pub(crate) fn at(x : usize, y : usize) -> Square {
+        debug_assert!(x < 8 && y < 8);
+        Square((8 * y + x) as u8)
+    }
+
+    // Returns all 64 squares, from A8 to H8 and then down the board rank by rank
+    pub fn all() -> impl Iterator<Item = Square> {
+        (0..64).map(Square)
+    }
+
+    pub fn x(&self) -> usize {
+        (self.0 % 8) as usize
+    }
+
+    pub fn y(&self) -> usize {
+        (self.0 / 8) as usize
+    }
+
+    pub fn coordinates(&self) -> (usize, usize) {
+        (self.x(), self.y())
+    }
+
+    // Returns 8 * y + x, from 0 for A8 to 63 for H1
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+
+    // Returns the bitboard with only this square set, which is the bit 0x80_00_00_00_00_00_00_00 >> index
+    pub fn bit(&self) -> u64 {
+        0x80_00_00_00_00_00_00_00u64 >> self.0
+    }
+
+    // Returns the file as an uppercase letter 'A' to 'H'
+    pub fn file(&self) -> char {
+        (b'A' + self.0 % 8) as char
+    }
+
+    // Returns the rank as a number from 1 to 8, 1 being white's side of the board
+    pub fn rank(&self) -> u8 {
+        8 - self.0 / 8
+    }
+}
+
+// Reads a square in standard chess coordinates, accepting either case for the file
+impl FromStr for Square {
+    type Err = ParseSquareError;
+
+    fn from_str(square : &str) -> Result<Square, ParseSquareError> {
+        let characters = square.chars().collect::<Vec<char>>();
+        let [file, rank] = characters[..] else {
+            return Err(ParseSquareError::InvalidLength(characters.len()));
+        };
+        let x = match file.to_ascii_uppercase() {
+            file @ 'A'..='H' => {file as usize - 'A' as usize},
+            _ => {return Err(ParseSquareError::InvalidFile(file))}
+        };
+        let y = match rank {
+            '1'..='8' => {'8' as usize - rank as usize},
+            _ => {return Err(ParseSquareError::InvalidRank(rank))}
+        };
+        return Ok(Square::at(x, y))
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.file(), self.rank())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_format() {
+        let square = "e2".parse::<Square>().unwrap();
+        assert!(square == Square::new(4, 6).unwrap());
+        assert!(square.coordinates() == (4, 6));
+        assert!(square.file() == 'E' && square.rank() == 2);
+        assert!(square.to_string() == "E2");
+        assert!("A8".parse::<Square>() == Ok(Square::new(0, 0).unwrap()));
+        assert!("h1".parse::<Square>() == Ok(Square::new(7, 7).unwrap()));
+
+        assert!("".parse::<Square>() == Err(ParseSquareError::InvalidLength(0)));
+        assert!("e".parse::<Square>() == Err(ParseSquareError::InvalidLength(1)));
+        assert!("e22".parse::<Square>() == Err(ParseSquareError::InvalidLength(3)));
+        assert!("Z9".parse::<Square>() == Err(ParseSquareError::InvalidFile('Z')));
+        assert!("é2".parse::<Square>() == Err(ParseSquareError::InvalidFile('é')));
+        assert!("E9".parse::<Square>() == Err(ParseSquareError::InvalidRank('9')));
+        assert!(Square::new(8, 0) == Err(ParseSquareError::OutOfBounds(8, 0)));
+    }
+
+    #[test]
+    fn all_squares() {
+        let squares = Square::all().collect::<Vec<Square>>();
+        assert!(squares.len() == 64);
+        assert!(squares[0].to_string() == "A8");
+        assert!(squares[63].to_string() == "H1");
+        for (index, square) in squares.into_iter().enumerate() {
+            assert!(square.to_string().parse::<Square>() == Ok(square));
+            assert!(8 * square.y() + square.x() == index);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+// A transposition table remembering what the search found out about positions it has seen before
+// The same position is often reached through different move orders, and each visit after the first can reuse the work
+
+use std::fmt;
+
+use crate::engine::MATE_THRESHOLD;
+use crate::Move;
+
+// How a stored score relates to the real score of the position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact, // The score is the real score
+    Lower, // A move was good enough to cut the search off, so the real score is at least this high
+    Upper // No move reached alpha, so the real score is at most this high
+}
+
+// What the search found out about one position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TableEntry {
+    pub key: u64, // Zobrist key of the position
+    pub depth: u32, // How many plies deep the position was searched
+    pub bound: Bound,
+    pub score: i32, // Mate scores count plies from this position rather than from the root of the search
+    pub best_move: Option<Move>, // The best move found, None if no move reached alpha
+    generation: u8 // The search the entry was stored in, so entries from old searches are replaced first
+}
+
+// A fixed-size table of positions, indexed by Zobrist key
+// When two positions share a slot the one searched deeper is kept, unless the other is from an older search
+#[derive(Clone)]
+pub struct TranspositionTable {
+    entries: Vec<Option<TableEntry>>,
+    generation: u8
+}
+
+impl fmt::Debug for TranspositionTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TranspositionTable").field("capacity", &self.capacity()).field("generation", &self.generation).finish()
+    }
+}
+
+impl TranspositionTable {
+    // Constructs an empty table taking up about the given number of megabytes, with room for at least one entry
+    pub fn new(megabytes : usize) -> TranspositionTable {
+        let capacity = (megabytes * 1024 * 1024 / std::mem::size_of::<Option<TableEntry>>()).max(1);
+        TranspositionTable { entries: vec![None; capacity], generation: 0 }
+    }
+
+    // Returns how many entries the table has room for
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    // Removes every entry
+    pub fn clear(&mut self) -> () {
+        self.entries.fill(None);
+        self.generation = 0;
+    }
+
+    // Marks the entries stored so far as old, to be called at the start of every search
+    pub fn new_search(&mut self) -> () {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    // Returns the entry for the position with the given key, if the table has one
+    // The score is adjusted so mate scores count plies from the root again, given the ply the position is at
+    pub fn probe(&self, key : u64, ply : i32) -> Option<TableEntry> {
+        let mut entry = self.entries[self.slot(key)].filter(|entry| entry.key == key)?;
+        entry.score = score_from_table(entry.score, ply);
+        return Some(entry)
+    }
+
+    // Stores what was found out about the position with the given key at the given ply
+    // An entry already in the slot is kept if it is from the current search and was searched deeper
+    pub fn store(&mut self, key : u64, depth : u32, bound : Bound, score : i32, best_move : Option<Move>, ply : i32) -> () {
+        let slot = self.slot(key);
+        let generation = self.generation;
+        let mut best_move = best_move;
+        if let Some(old) = self.entries[slot] {
+            if old.key != key && old.generation == generation && old.depth > depth {
+                return;
+            }
+            // A search that failed low has no best move, but an earlier one may have
+            if old.key == key && best_move.is_none() {
+                best_move = old.best_move;
+            }
+        }
+        let score = score_to_table(score, ply);
+        self.entries[slot] = Some(TableEntry { key, depth, bound, score, best_move, generation });
+    }
+
+    fn slot(&self, key : u64) -> usize {
+        (key % self.entries.len() as u64) as usize
+    }
+}
+
+// Mate scores are stored as plies to mate from the position itself, since the same position can be reached at
+// different plies, and turned back into plies from the root when read
+fn score_to_table(score : i32, ply : i32) -> i32 {
+    if score >= MATE_THRESHOLD {
+        return score + ply;
+    }
+    if score <= -MATE_THRESHOLD {
+        return score - ply;
+    }
+    return score
+}
+
+fn score_from_table(score : i32, ply : i32) -> i32 {
+    if score >= MATE_THRESHOLD {
+        return score - ply;
+    }
+    if score <= -MATE_THRESHOLD {
+        return score + ply;
+    }
+    return score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MATE_SCORE;
+
+    #[test]
+    fn store_and_probe() {
+        let mut table = TranspositionTable::new(1);
+        assert!(table.capacity() > 1000);
+        let chess_move = Move::from_uci("e2e4").unwrap();
+        assert!(table.probe(42, 0).is_none());
+        table.store(42, 3, Bound::Exact, 25, Some(chess_move), 0);
+        let entry = table.probe(42, 0).unwrap();
+        assert!(entry.depth == 3 && entry.bound == Bound::Exact && entry.score == 25);
+        assert!(entry.best_move == Some(chess_move));
+        // A different key in the same slot is not mistaken for the stored position
+        assert!(table.probe(42 + table.capacity() as u64, 0).is_none());
+        // Failing low again keeps the best move found before
+        table.store(42, 4, Bound::Upper, 10, None, 0);
+        assert!(table.probe(42, 0).unwrap().best_move == Some(chess_move));
+        table.clear();
+        assert!(table.probe(42, 0).is_none());
+    }
+
+    #[test]
+    fn replacement() {
+        let mut table = TranspositionTable::new(0);
+        assert!(table.capacity() == 1);
+        table.store(1, 5, Bound::Exact, 0, None, 0);
+        // A shallower search of another position does not push out a deeper one from the same search
+        table.store(2, 2, Bound::Exact, 0, None, 0);
+        assert!(table.probe(1, 0).is_some() && table.probe(2, 0).is_none());
+        table.store(2, 5, Bound::Exact, 0, None, 0);
+        assert!(table.probe(2, 0).is_some());
+        // Entries from an older search always make room
+        table.new_search();
+        table.store(3, 1, Bound::Lower, 0, None, 0);
+        assert!(table.probe(3, 0).is_some());
+        // The same position is always updated
+        table.store(3, 0, Bound::Upper, 7, None, 0);
+        assert!(table.probe(3, 0).unwrap().score == 7);
+    }
+
+    #[test]
+    fn mate_scores() {
+        // Mate in three plies found five plies into the search is mate in three from wherever the position comes up
+        let mut table = TranspositionTable::new(1);
+        table.store(7, 4, Bound::Exact, MATE_SCORE - 8, None, 5);
+        assert!(table.probe(7, 5).unwrap().score == MATE_SCORE - 8);
+        assert!(table.probe(7, 1).unwrap().score == MATE_SCORE - 4);
+        table.store(8, 4, Bound::Exact, -(MATE_SCORE - 8), None, 5);
+        assert!(table.probe(8, 3).unwrap().score == -(MATE_SCORE - 6));
+        table.store(9, 4, Bound::Exact, 150, None, 5);
+        assert!(table.probe(9, 1).unwrap().score == 150);
+    }
+}
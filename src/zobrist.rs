@@ -0,0 +1,126 @@
+// Zobrist hashing, which identifies a position by XORing together a random number for each thing in it
+// A move only changes a few of those things, so the key is updated as pieces move instead of being recomputed
+
+use crate::bitboard::{color_index, piece_index, square_bit, squares};
+use crate::{CastlingRights, Color, Game, Piece};
+
+// One number for each piece of each color on each square, then the side to move, the four castling rights and
+// the eight files an en passant capture can happen on
+const PIECE_KEYS : usize = 2 * 6 * 64;
+const SIDE_KEY : usize = PIECE_KEYS;
+const CASTLING_KEYS : usize = SIDE_KEY + 1;
+const EN_PASSANT_KEYS : usize = CASTLING_KEYS + 4;
+const KEY_COUNT : usize = EN_PASSANT_KEYS + 8;
+
+// Filled in at compile time by a xorshift64* generator with a fixed seed, so keys are the same on every run
+const KEYS : [u64; KEY_COUNT] = {
+    let mut keys = [0; KEY_COUNT];
+    let mut state : u64 = 0x5DEE_CE66_D1CE_4E5B;
+    let mut i = 0;
+    while i < KEY_COUNT {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        keys[i] = state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        i += 1;
+    }
+    keys
+};
+
+// Returns the key for the given piece of the given color standing on the square at numerical coordinates (x, y)
+pub(crate) fn piece_key(piece : Piece, color : Color, x : usize, y : usize) -> u64 {
+    KEYS[(color_index(color) * 6 + piece_index(piece)) * 64 + 8 * y + x]
+}
+
+// Returns the key XORed in while black is to move
+pub(crate) fn side_key() -> u64 {
+    KEYS[SIDE_KEY]
+}
+
+// Returns the combined key of the castling rights that are still available
+pub(crate) fn castling_key(castling : CastlingRights) -> u64 {
+    let rights = [castling.white_king_side, castling.white_queen_side, castling.black_king_side, castling.black_queen_side];
+    let mut key = 0;
+    for (i, available) in rights.into_iter().enumerate() {
+        if available {
+            key ^= KEYS[CASTLING_KEYS + i];
+        }
+    }
+    return key
+}
+
+// Returns the key for the file of the en passant square, if there is one
+pub(crate) fn en_passant_key(en_passant : Option<(usize, usize)>) -> u64 {
+    match en_passant {
+        Some((x, _)) => {KEYS[EN_PASSANT_KEYS + x]},
+        None => {0}
+    }
+}
+
+impl Game {
+    // Returns the Zobrist key of the current position
+    // Positions with the same pieces on the same squares, player to move, castling rights and en passant file share a
+    // key, and different positions almost never do
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    // Computes the Zobrist key of the current position from scratch
+    pub(crate) fn compute_hash(&self) -> u64 {
+        let mut key = castling_key(self.castling) ^ en_passant_key(self.en_passant);
+        if self.player == Color::Black {
+            key ^= side_key();
+        }
+        for (x, y) in squares(self.black | self.white) {
+            let color = if self.black & square_bit(x, y) != 0 {Color::Black} else {Color::White};
+            key ^= piece_key(self.board[y][x].unwrap(), color, x, y);
+        }
+        return key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incremental() {
+        // Moving a knight out and back gives the starting key again, with the same player to move
+        let mut game = Game::new();
+        let start = game.hash();
+        assert!(start == game.compute_hash());
+        for san in ["Nf3", "Nf6", "Ng1"] {
+            assert!(game.make_san_move(san).is_ok());
+            assert!(game.hash() == game.compute_hash());
+            assert!(game.hash() != start);
+        }
+        assert!(game.make_san_move("Ng8").is_ok());
+        assert!(game.hash() == start);
+        assert!(game.make_san_move("Nc3").is_ok());
+        assert!(game.undo().is_some());
+        assert!(game.hash() == start);
+
+        // The same placement differs by side to move, castling rights and en passant file
+        let position = Game::from_fen("r3k2r/8/8/8/4Pp2/8/8/R3K2R b KQkq e3 0 1").unwrap().hash();
+        for other in ["r3k2r/8/8/8/4Pp2/8/8/R3K2R w KQkq - 0 1", "r3k2r/8/8/8/4Pp2/8/8/R3K2R b Qkq e3 0 1",
+            "r3k2r/8/8/8/4Pp2/8/8/R3K2R b KQkq - 0 1"] {
+            assert!(Game::from_fen(other).unwrap().hash() != position);
+        }
+        assert!(Game::from_fen("r3k2r/8/8/8/4Pp2/8/8/R3K2R b KQkq e3 5 20").unwrap().hash() == position);
+    }
+
+    #[test]
+    fn transposition() {
+        // Different move orders reaching the same position give the same key
+        let mut first = Game::new();
+        let mut second = Game::new();
+        for san in ["e4", "e5", "Nf3", "Nc6", "Bb5"] {
+            assert!(first.make_san_move(san).is_ok());
+        }
+        for san in ["Nf3", "Nc6", "e4", "e5", "Bb5"] {
+            assert!(second.make_san_move(san).is_ok());
+        }
+        assert!(first.hash() == second.hash());
+        assert!(first.hash() == Game::from_fen(&first.to_fen()).unwrap().hash());
+    }
+}